@@ -2,12 +2,26 @@ use super::ShellContext;
 use std::{collections::HashMap, sync::Arc};
 
 // mod cat;
+mod bg;
 mod cd;
+mod disown;
 mod echo;
 mod exit;
+mod export;
+mod fg;
 mod history;
+mod jobs;
+mod kill;
+mod r#let;
 mod pwd;
+mod read;
+mod readonly;
+mod set;
+mod shopt;
+mod trap;
 mod r#type;
+mod unset;
+mod wait;
 
 macro_rules! registry {
     ( $( $cmd:path ),* $(,)? ) => {{
@@ -27,13 +41,28 @@ pub fn init_registry() -> Registry {
         pwd::Pwd,
         cd::Cd,
         history::History,
+        r#let::Let,
+        shopt::Shopt,
+        read::Read,
+        set::Set,
+        jobs::Jobs,
+        fg::Fg,
+        bg::Bg,
+        trap::Trap,
+        kill::Kill,
+        wait::Wait,
+        disown::Disown,
+        export::Export,
+        unset::Unset,
+        readonly::Readonly,
     ]
 }
 
 pub trait BuiltinCommand {
     fn name(&self) -> &str;
 
-    fn execute(&self, args: &[String], ctx: &mut ShellContext);
+    /// Run the builtin and return its exit status.
+    fn execute(&self, args: &[String], ctx: &mut ShellContext) -> i32;
 }
 
 pub struct Registry {
@@ -58,7 +87,7 @@ impl Registry {
     }
 
     pub fn check_builtin(&self, name: &str) -> bool {
-        self.commands.get(name).is_some()
+        self.commands.contains_key(name)
     }
 
     pub fn command_names(&self) -> impl Iterator<Item = &str> {
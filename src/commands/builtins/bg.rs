@@ -0,0 +1,50 @@
+use super::{BuiltinCommand, ShellContext};
+use crate::job::JobState;
+
+pub struct Bg;
+
+impl BuiltinCommand for Bg {
+    fn name(&self) -> &str {
+        "bg"
+    }
+    fn execute(&self, args: &[String], ctx: &mut ShellContext) -> i32 {
+        if !ctx.job_control() {
+            eprintln!("{}: no job control", self.name());
+            return 1;
+        }
+
+        let current = ["%+".to_string()];
+        let specs = if args.is_empty() { &current[..] } else { args };
+
+        let mut status = 0;
+        for spec in specs {
+            let jobs = ctx.jobs_mut();
+            let id = match jobs.find(spec) {
+                Ok(id) => id,
+                Err(_) if args.is_empty() => {
+                    eprintln!("{}: current: no such job", self.name());
+                    return 1;
+                }
+                Err(e) => {
+                    eprintln!("{}: {e}", self.name());
+                    status = 1;
+                    continue;
+                }
+            };
+
+            let marker = jobs.marker(id);
+            let Some(job) = jobs.get_mut(id) else {
+                continue;
+            };
+            if job.state == JobState::Running {
+                eprintln!("{}: job {id} already in background", self.name());
+                continue;
+            }
+
+            job.resume();
+            println!("[{id}]{marker} {} &", job.command);
+        }
+
+        status
+    }
+}
@@ -7,42 +7,52 @@ impl BuiltinCommand for Cd {
     fn name(&self) -> &str {
         "cd"
     }
-    fn execute(&self, args: &[String], ctx: &mut ShellContext) {
+    fn execute(&self, args: &[String], ctx: &mut ShellContext) -> i32 {
         if args.len() > 1 {
-            println!("{}: too many arguments", self.name());
+            eprintln!("{}: too many arguments", self.name());
+            return 1;
         }
 
         let target = match args.first().map(String::as_str) {
-            Some("~") => match ctx.env("HOME") {
-                Some(home) => PathBuf::from(home),
-                None => {
-                    eprintln!("{}: HOME not set", self.name());
-                    return;
-                }
-            },
             Some(path) => PathBuf::from(path),
             None => match ctx.env("HOME") {
                 Some(home) => PathBuf::from(home),
                 None => {
                     eprintln!("{}: HOME not set", self.name());
-                    return;
+                    return 1;
                 }
             },
         };
 
-        if let Err(_) = std::env::set_current_dir(&target) {
+        if std::env::set_current_dir(&target).is_err() {
             eprintln!(
                 "{}: {}: No such file or directory",
                 self.name(),
                 target.to_str().unwrap()
             );
-            return;
+            return 1;
         }
 
         match std::env::current_dir() {
-            Ok(abs) => ctx.set_cwd(&abs),
+            Ok(abs) => {
+                // Keep $PWD/$OLDPWD in sync now that they can be expanded,
+                // the directory changes even if they're readonly
+                let old = ctx.cwd().to_string_lossy().to_string();
+                let old_pwd = ctx.assign("OLDPWD", &old);
+                let pwd = ctx.assign("PWD", &abs.to_string_lossy());
+                ctx.set_cwd(&abs);
+
+                match old_pwd.and(pwd) {
+                    Ok(()) => 0,
+                    Err(e) => {
+                        eprintln!("{}: {e}", self.name());
+                        1
+                    }
+                }
+            }
             Err(_) => {
                 eprintln!("{}: failed to get current directory", self.name());
+                1
             }
         }
     }
@@ -0,0 +1,122 @@
+use super::{BuiltinCommand, ShellContext};
+
+pub struct Disown;
+
+impl Disown {
+    // Options come first, the rest are job specs
+    fn parse_disown_args(args: &[String]) -> Result<(DisownFlags, &[String]), String> {
+        let mut flags = DisownFlags::default();
+
+        for (i, arg) in args.iter().enumerate() {
+            let Some(letters) = arg.strip_prefix('-').filter(|f| !f.is_empty()) else {
+                return Ok((flags, &args[i..]));
+            };
+
+            for flag in letters.chars() {
+                match flag {
+                    'a' => flags.all = true,
+                    'h' => flags.nohup = true,
+                    _ => return Err(format!("disown: -{flag}: invalid option")),
+                }
+            }
+        }
+
+        Ok((flags, &[]))
+    }
+}
+
+impl BuiltinCommand for Disown {
+    fn name(&self) -> &str {
+        "disown"
+    }
+    fn execute(&self, args: &[String], ctx: &mut ShellContext) -> i32 {
+        let (flags, specs) = match Self::parse_disown_args(args) {
+            Ok(a) => a,
+            Err(e) => {
+                eprintln!("{e}");
+                return 2;
+            }
+        };
+
+        // The current job unless others are named
+        let jobs = ctx.jobs_mut();
+        let mut status = 0;
+        let ids: Vec<usize> = if flags.all {
+            jobs.iter().map(|job| job.id).collect()
+        } else if specs.is_empty() {
+            match jobs.find("%+") {
+                Ok(id) => vec![id],
+                Err(_) => {
+                    eprintln!("{}: current: no such job", self.name());
+                    return 1;
+                }
+            }
+        } else {
+            specs
+                .iter()
+                .filter_map(|spec| match jobs.find(spec) {
+                    Ok(id) => Some(id),
+                    Err(e) => {
+                        eprintln!("{}: {e}", self.name());
+                        status = 1;
+                        None
+                    }
+                })
+                .collect()
+        };
+
+        for id in ids {
+            match flags.nohup {
+                true => {
+                    if let Some(job) = jobs.get_mut(id) {
+                        job.nohup = true;
+                    }
+                }
+                false => {
+                    jobs.remove(id);
+                }
+            }
+        }
+
+        status
+    }
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct DisownFlags {
+    // `-a`, every job
+    all: bool,
+    // `-h`, keep the job but don't send it SIGHUP on exit
+    nohup: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::job::List;
+
+    #[test]
+    fn test_disown() {
+        let args = |args: &[&str]| args.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            Ok((
+                DisownFlags {
+                    all: true,
+                    nohup: true
+                },
+                &args(&[])[..]
+            )),
+            Disown::parse_disown_args(&args(&["-ah"]))
+        );
+        assert!(Disown::parse_disown_args(&args(&["-r"])).is_err());
+
+        let mut ctx = ShellContext::new();
+        List::run_source(
+            "true & true & true & disown -h %1; disown; disown %9",
+            &mut ctx,
+        );
+        assert_eq!(1, ctx.status());
+        let jobs: Vec<_> = ctx.jobs().iter().map(|job| (job.id, job.nohup)).collect();
+        assert_eq!(vec![(1, true), (2, false)], jobs);
+    }
+}
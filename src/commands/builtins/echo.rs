@@ -5,7 +5,8 @@ impl BuiltinCommand for Echo {
     fn name(&self) -> &str {
         "echo"
     }
-    fn execute(&self, args: &[String], _ctx: &mut ShellContext) {
+    fn execute(&self, args: &[String], _ctx: &mut ShellContext) -> i32 {
         println!("{}", args.join(" "));
+        0
     }
 }
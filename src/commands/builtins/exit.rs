@@ -1,5 +1,3 @@
-use std::path::PathBuf;
-
 use super::{BuiltinCommand, ShellContext};
 
 pub struct Exit;
@@ -8,8 +6,25 @@ impl BuiltinCommand for Exit {
     fn name(&self) -> &str {
         "exit"
     }
-    fn execute(&self, _args: &[String], ctx: &mut ShellContext) {
+    fn execute(&self, args: &[String], ctx: &mut ShellContext) -> i32 {
+        // Only the interactive shell itself has jobs to leave behind
+        if ctx.job_control() && ctx.jobs_mut().warn_stopped() {
+            return 1;
+        }
+
+        // Defaults to the status of the last command
+        let status = match args.first() {
+            Some(arg) => match arg.parse::<i64>() {
+                Ok(n) => n as u8 as i32,
+                Err(_) => {
+                    eprintln!("{}: {arg}: numeric argument required", self.name());
+                    2
+                }
+            },
+            None => ctx.status(),
+        };
+
         ctx.shutdown();
-        std::process::exit(0);
+        std::process::exit(status);
     }
 }
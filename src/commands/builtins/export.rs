@@ -0,0 +1,159 @@
+use super::{BuiltinCommand, ShellContext};
+use crate::context::Var;
+use crate::expand::is_name;
+
+pub struct Export;
+
+impl Export {
+    // Options come first, the rest are names to export
+    fn parse_export_args(args: &[String]) -> Result<(ExportFlags, &[String]), String> {
+        let mut flags = ExportFlags::default();
+
+        for (i, arg) in args.iter().enumerate() {
+            if arg == "--" {
+                return Ok((flags, &args[i + 1..]));
+            }
+            let Some(letters) = arg.strip_prefix('-').filter(|f| !f.is_empty()) else {
+                return Ok((flags, &args[i..]));
+            };
+
+            for flag in letters.chars() {
+                match flag {
+                    'n' => flags.unexport = true,
+                    'p' => flags.print = true,
+                    _ => return Err(format!("export: -{flag}: invalid option")),
+                }
+            }
+        }
+
+        Ok((flags, &[]))
+    }
+}
+
+impl BuiltinCommand for Export {
+    fn name(&self) -> &str {
+        "export"
+    }
+    fn execute(&self, args: &[String], ctx: &mut ShellContext) -> i32 {
+        let (flags, names) = match Self::parse_export_args(args) {
+            Ok(a) => a,
+            Err(e) => {
+                eprintln!("{e}");
+                return 2;
+            }
+        };
+
+        if flags.print || names.is_empty() {
+            print_declarations(ctx, |var| var.exported);
+            return 0;
+        }
+
+        let mut status = 0;
+        for arg in names {
+            match assign_arg(arg, ctx) {
+                Ok(name) => ctx.set_exported(name, !flags.unexport),
+                Err(e) => {
+                    eprintln!("{}: {e}", self.name());
+                    status = 1;
+                }
+            }
+        }
+
+        status
+    }
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct ExportFlags {
+    // `-n`, keep the names in the shell only
+    unexport: bool,
+    // `-p`, list exported variables
+    print: bool,
+}
+
+/// Assign `NAME=value` from an argument of `export` or `readonly`, returning
+/// the name. A bare `NAME` keeps its value.
+pub(super) fn assign_arg<'a>(arg: &'a str, ctx: &mut ShellContext) -> Result<&'a str, String> {
+    let (name, value) = match arg.split_once('=') {
+        Some((name, value)) => (name, Some(value)),
+        None => (arg, None),
+    };
+    if !is_name(name) {
+        return Err(format!("`{arg}': not a valid identifier"));
+    }
+
+    if let Some(value) = value {
+        ctx.assign(name, value)?;
+    }
+    Ok(name)
+}
+
+/// Print the variables `filter` selects, by name, as `declare` commands that
+/// would recreate them.
+pub(super) fn print_declarations(ctx: &ShellContext, filter: impl Fn(&Var) -> bool) {
+    let mut vars: Vec<_> = ctx.vars().filter(|(_, var)| filter(var)).collect();
+    vars.sort_by_key(|(name, _)| *name);
+
+    for (name, var) in vars {
+        println!("{}", declaration(name, var));
+    }
+}
+
+// E.g. `declare -rx NAME="value"`, escaped for double quotes
+fn declaration(name: &str, var: &Var) -> String {
+    let mut flags = String::from("-");
+    if var.readonly {
+        flags.push('r');
+    }
+    if var.exported {
+        flags.push('x');
+    }
+
+    match &var.value {
+        Some(value) => {
+            let mut escaped = String::new();
+            for c in value.chars() {
+                if matches!(c, '"' | '\\' | '$' | '`') {
+                    escaped.push('\\');
+                }
+                escaped.push(c);
+            }
+            format!("declare {flags} {name}=\"{escaped}\"")
+        }
+        None => format!("declare {flags} {name}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::job::List;
+
+    #[test]
+    fn test_export() {
+        let args = |args: &[&str]| args.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+        let input = args(&["-np", "--", "-A"]);
+        let parsed = Export::parse_export_args(&input);
+        let flags = ExportFlags {
+            unexport: true,
+            print: true,
+        };
+        assert_eq!(Ok((flags, &args(&["-A"])[..])), parsed);
+
+        let mut ctx = ShellContext::new();
+        List::run_source("A=1; B=2 C=3; export A B=\"$C\" D; export -n C", &mut ctx);
+        let exported = |ctx: &ShellContext, name| ctx.exported().any(|(n, _)| n == name);
+        assert!(exported(&ctx, "A") && exported(&ctx, "B"));
+        assert!(!exported(&ctx, "C") && !exported(&ctx, "D"));
+        assert_eq!(Some("3"), ctx.env("B"));
+
+        // Marked before it has a value
+        List::run_source("D='say \"$A\"'", &mut ctx);
+        assert!(exported(&ctx, "D"));
+        let (_, d) = ctx.vars().find(|(name, _)| *name == "D").unwrap();
+        assert_eq!(r#"declare -x D="say \"\$A\"""#, declaration("D", d));
+
+        List::run_source("export 1A=x", &mut ctx);
+        assert_eq!(1, ctx.status());
+    }
+}
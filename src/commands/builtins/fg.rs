@@ -0,0 +1,41 @@
+use super::{BuiltinCommand, ShellContext};
+use crate::job::Job;
+
+pub struct Fg;
+
+impl BuiltinCommand for Fg {
+    fn name(&self) -> &str {
+        "fg"
+    }
+    fn execute(&self, args: &[String], ctx: &mut ShellContext) -> i32 {
+        if !ctx.job_control() {
+            eprintln!("{}: no job control", self.name());
+            return 1;
+        }
+        if args.len() > 1 {
+            eprintln!("{}: too many arguments", self.name());
+            return 2;
+        }
+
+        let found = match args.first() {
+            Some(spec) => ctx.jobs().find(spec),
+            None => ctx
+                .jobs()
+                .find("%+")
+                .map_err(|_| "current: no such job".to_string()),
+        };
+        let id = match found {
+            Ok(id) => id,
+            Err(e) => {
+                eprintln!("{}: {e}", self.name());
+                return 1;
+            }
+        };
+        let Some(job) = ctx.jobs_mut().remove(id) else {
+            return 1;
+        };
+
+        println!("{}", job.command);
+        Job::foreground(job, ctx)
+    }
+}
@@ -1,6 +1,5 @@
 use super::{BuiltinCommand, ShellContext};
 use std::path::PathBuf;
-use std::usize;
 
 pub struct History;
 
@@ -68,35 +67,32 @@ impl BuiltinCommand for History {
     fn name(&self) -> &str {
         "history"
     }
-    fn execute(&self, args: &[String], ctx: &mut ShellContext) {
+    fn execute(&self, args: &[String], ctx: &mut ShellContext) -> i32 {
         let action = match Self::parse_history_args(args) {
             Ok(a) => a,
             Err(e) => {
                 eprintln!("{e}");
-                return;
+                return 2;
             }
         };
 
-        match action {
-            HistoryAction::Clear => ctx.clear_history(),
-
-            HistoryAction::Read(file) => {
-                if let Err(e) = ctx.read_history(&file) {
-                    eprintln!("{}: failed to read {}", self.name(), e)
-                }
+        let result = match action {
+            HistoryAction::Clear => {
+                ctx.clear_history();
+                Ok(())
             }
 
-            HistoryAction::Write(file) => {
-                if let Err(e) = ctx.write_history(&file) {
-                    eprintln!("{}: failed to write {}", self.name(), e)
-                }
-            }
+            HistoryAction::Read(file) => ctx
+                .read_history(&file)
+                .map_err(|e| eprintln!("{}: failed to read {}", self.name(), e)),
 
-            HistoryAction::Append(file) => {
-                if let Err(e) = ctx.flush_history(&file) {
-                    eprintln!("{}: failed to append {}", self.name(), e)
-                }
-            }
+            HistoryAction::Write(file) => ctx
+                .write_history(&file)
+                .map_err(|e| eprintln!("{}: failed to write {}", self.name(), e)),
+
+            HistoryAction::Append(file) => ctx
+                .flush_history(&file)
+                .map_err(|e| eprintln!("{}: failed to append {}", self.name(), e)),
 
             HistoryAction::List { limit } => {
                 let entries = ctx.historys();
@@ -105,8 +101,11 @@ impl BuiltinCommand for History {
                 for (idx, entry) in (start + 1..).zip(entries.iter().skip(start)) {
                     println!("    {}  {}", idx, entry);
                 }
+                Ok(())
             }
-        }
+        };
+
+        result.map_or(1, |_| 0)
     }
 }
 
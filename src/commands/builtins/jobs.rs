@@ -0,0 +1,117 @@
+use super::{BuiltinCommand, ShellContext};
+
+pub struct Jobs;
+
+impl Jobs {
+    // Options come first, the rest are job specs
+    fn parse_jobs_args(args: &[String]) -> Result<(JobsFormat, &[String]), String> {
+        let mut format = JobsFormat::Normal;
+
+        for (i, arg) in args.iter().enumerate() {
+            let Some(flags) = arg.strip_prefix('-').filter(|f| !f.is_empty()) else {
+                return Ok((format, &args[i..]));
+            };
+
+            for flag in flags.chars() {
+                format = match flag {
+                    'l' => JobsFormat::Long,
+                    'p' => JobsFormat::Pids,
+                    _ => return Err(format!("jobs: -{flag}: invalid option")),
+                };
+            }
+        }
+
+        Ok((format, &[]))
+    }
+}
+
+impl BuiltinCommand for Jobs {
+    fn name(&self) -> &str {
+        "jobs"
+    }
+    fn execute(&self, args: &[String], ctx: &mut ShellContext) -> i32 {
+        let (format, specs) = match Self::parse_jobs_args(args) {
+            Ok(a) => a,
+            Err(e) => {
+                eprintln!("{e}");
+                return 2;
+            }
+        };
+
+        let jobs = ctx.jobs_mut();
+        jobs.update();
+
+        // Every job unless some are named
+        let mut status = 0;
+        let ids: Vec<usize> = match specs.is_empty() {
+            true => jobs.iter().map(|job| job.id).collect(),
+            false => specs
+                .iter()
+                .filter_map(|spec| match jobs.find(spec) {
+                    Ok(id) => Some(id),
+                    Err(e) => {
+                        eprintln!("{}: {e}", self.name());
+                        status = 1;
+                        None
+                    }
+                })
+                .collect(),
+        };
+
+        for job in jobs.iter().filter(|job| ids.contains(&job.id)) {
+            match format {
+                JobsFormat::Pids => println!("{}", job.pids[0]),
+                JobsFormat::Normal => println!("{}", job.describe(jobs.marker(job.id), false)),
+                JobsFormat::Long => println!("{}", job.describe(jobs.marker(job.id), true)),
+            }
+        }
+
+        // Finished jobs have been reported now
+        for id in ids {
+            if jobs
+                .iter()
+                .any(|job| job.id == id && job.state.is_finished())
+            {
+                jobs.remove(id);
+            }
+        }
+
+        status
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum JobsFormat {
+    Normal,
+    // `-l` adds the process id
+    Long,
+    // `-p` shows only the process id
+    Pids,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse() {
+        let specs = args(&["%1", "-l"]);
+        assert_eq!(
+            Ok((JobsFormat::Normal, &[][..])),
+            Jobs::parse_jobs_args(&[])
+        );
+        assert_eq!(
+            Ok((JobsFormat::Long, &specs[..])),
+            Jobs::parse_jobs_args(&args(&["-l", "%1", "-l"]))
+        );
+        assert_eq!(
+            Ok((JobsFormat::Pids, &[][..])),
+            Jobs::parse_jobs_args(&args(&["-lp"]))
+        );
+        assert!(Jobs::parse_jobs_args(&args(&["-x"])).is_err());
+    }
+}
@@ -0,0 +1,199 @@
+use super::{BuiltinCommand, ShellContext};
+use crate::job::JobState;
+use crate::signal;
+
+use libc::{EPERM, ESRCH, SIGHUP, SIGTERM, c_int};
+use std::io;
+
+const USAGE: &str =
+    "kill: usage: kill [-s sigspec | -sigspec] pid | jobspec ... or kill -l [sigspec]";
+
+pub struct Kill;
+
+impl Kill {
+    fn parse_kill_args(args: &[String]) -> Result<KillAction, String> {
+        // Only one option is taken, so a negative number after it is a
+        // process group
+        let (sig, targets) = match args.first().map(String::as_str) {
+            Some("-l" | "-L") => return Ok(KillAction::List(args[1..].to_vec())),
+            Some(flag @ ("-s" | "-n")) => {
+                let spec = args
+                    .get(1)
+                    .ok_or_else(|| format!("kill: {flag}: option requires an argument"))?;
+                (Self::parse_signal(spec)?, &args[2..])
+            }
+            Some("--") => (SIGTERM, &args[1..]),
+            Some(flag) if flag.starts_with('-') && flag.len() > 1 => {
+                (Self::parse_signal(&flag[1..])?, &args[1..])
+            }
+            _ => (SIGTERM, args),
+        };
+
+        if targets.is_empty() {
+            return Err(USAGE.to_string());
+        }
+
+        Ok(KillAction::Send {
+            signal: sig,
+            targets: targets.to_vec(),
+        })
+    }
+
+    // 0 sends nothing, it only checks that the target can be signalled
+    fn parse_signal(spec: &str) -> Result<c_int, String> {
+        match spec {
+            "0" => Ok(0),
+            _ => signal::from_name(spec)
+                .ok_or_else(|| format!("kill: {spec}: invalid signal specification")),
+        }
+    }
+
+    // `kill -l`, every signal, or the name of each number and the number of
+    // each name given
+    fn list(&self, specs: &[String]) -> i32 {
+        if specs.is_empty() {
+            let signals: Vec<String> = signal::all()
+                .map(|(sig, name)| format!("{sig:2}) SIG{name}"))
+                .collect();
+            for row in signals.chunks(5) {
+                println!("{}", row.join("\t"));
+            }
+            return 0;
+        }
+
+        let mut status = 0;
+        for spec in specs {
+            let found = match spec.parse::<c_int>() {
+                // An exit status of a job a signal killed names that signal
+                Ok(number) => signal::name(if number > 128 { number - 128 } else { number })
+                    .map(str::to_string),
+                Err(_) => signal::from_name(spec).map(|sig| sig.to_string()),
+            };
+            match found {
+                Some(found) => println!("{found}"),
+                None => {
+                    eprintln!("{}: {spec}: invalid signal specification", self.name());
+                    status = 1;
+                }
+            }
+        }
+
+        status
+    }
+
+    fn send(&self, sig: c_int, target: &str, ctx: &mut ShellContext) -> Result<(), String> {
+        if target.starts_with('%') {
+            let jobs = ctx.jobs_mut();
+            let id = jobs.find(target)?;
+            let Some(job) = jobs.get_mut(id) else {
+                return Err(format!("{target}: no such job"));
+            };
+
+            job.signal(sig)
+                .map_err(|e| format!("{target}: {}", Self::describe(&e)))?;
+            // A stopped job would only act on these once continued
+            if job.state == JobState::Stopped && matches!(sig, SIGTERM | SIGHUP) {
+                job.resume();
+            }
+            return Ok(());
+        }
+
+        let Ok(pid) = target.parse::<i32>() else {
+            return Err(format!("{target}: arguments must be process or job IDs"));
+        };
+        match unsafe { libc::kill(pid, sig) } {
+            -1 => Err(format!(
+                "({pid}) - {}",
+                Self::describe(&io::Error::last_os_error())
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    fn describe(e: &io::Error) -> String {
+        match e.raw_os_error() {
+            Some(ESRCH) => "No such process".to_string(),
+            Some(EPERM) => "Operation not permitted".to_string(),
+            _ => e.to_string(),
+        }
+    }
+}
+
+impl BuiltinCommand for Kill {
+    fn name(&self) -> &str {
+        "kill"
+    }
+    fn execute(&self, args: &[String], ctx: &mut ShellContext) -> i32 {
+        let (sig, targets) = match Self::parse_kill_args(args) {
+            Ok(KillAction::List(specs)) => return self.list(&specs),
+            Ok(KillAction::Send { signal, targets }) => (signal, targets),
+            Err(e) => {
+                eprintln!("{e}");
+                return 2;
+            }
+        };
+
+        let mut status = 0;
+        for target in &targets {
+            if let Err(e) = self.send(sig, target, ctx) {
+                eprintln!("{}: {e}", self.name());
+                status = 1;
+            }
+        }
+
+        status
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum KillAction {
+    // `kill -l [sigspec...]`
+    List(Vec<String>),
+    Send { signal: c_int, targets: Vec<String> },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libc::{SIGINT, SIGKILL, SIGUSR1};
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    fn send(signal: c_int, targets: &[&str]) -> Result<KillAction, String> {
+        Ok(KillAction::Send {
+            signal,
+            targets: args(targets),
+        })
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            send(SIGTERM, &["%1", "123"]),
+            Kill::parse_kill_args(&args(&["%1", "123"]))
+        );
+        assert_eq!(
+            send(SIGKILL, &["%1"]),
+            Kill::parse_kill_args(&args(&["-9", "%1"]))
+        );
+        assert_eq!(
+            send(SIGINT, &["%+"]),
+            Kill::parse_kill_args(&args(&["-s", "INT", "%+"]))
+        );
+        assert_eq!(
+            send(SIGUSR1, &["-42"]),
+            Kill::parse_kill_args(&args(&["-USR1", "-42"]))
+        );
+        assert_eq!(send(0, &["1"]), Kill::parse_kill_args(&args(&["-0", "1"])));
+        assert_eq!(
+            Ok(KillAction::List(args(&["130"]))),
+            Kill::parse_kill_args(&args(&["-l", "130"]))
+        );
+
+        assert!(Kill::parse_kill_args(&args(&["-NOPE", "1"])).is_err());
+        assert!(Kill::parse_kill_args(&args(&["-s"])).is_err());
+        assert!(Kill::parse_kill_args(&args(&["-9"])).is_err());
+    }
+}
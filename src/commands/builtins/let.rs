@@ -0,0 +1,31 @@
+use super::{BuiltinCommand, ShellContext};
+use crate::expand::evaluate_arith;
+
+pub struct Let;
+
+impl BuiltinCommand for Let {
+    fn name(&self) -> &str {
+        "let"
+    }
+    fn execute(&self, args: &[String], ctx: &mut ShellContext) -> i32 {
+        if args.is_empty() {
+            eprintln!("{}: expression expected", self.name());
+            return 1;
+        }
+
+        // Each argument is evaluated on its own, as in `let a=1 b=a+1`
+        let mut last = 0;
+        for arg in args {
+            match evaluate_arith(arg, ctx) {
+                Ok(value) => last = value,
+                Err(e) => {
+                    eprintln!("{}: {e}", self.name());
+                    return 1;
+                }
+            }
+        }
+
+        // Succeeds when the last expression is non-zero
+        (last == 0) as i32
+    }
+}
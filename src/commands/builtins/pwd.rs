@@ -6,7 +6,8 @@ impl BuiltinCommand for Pwd {
     fn name(&self) -> &str {
         "pwd"
     }
-    fn execute(&self, _args: &[String], ctx: &mut ShellContext) {
+    fn execute(&self, _args: &[String], ctx: &mut ShellContext) -> i32 {
         println!("{}", ctx.cwd().to_str().unwrap());
+        0
     }
 }
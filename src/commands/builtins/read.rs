@@ -0,0 +1,111 @@
+use super::{BuiltinCommand, ShellContext};
+use std::io::{self, Write};
+
+pub struct Read;
+
+impl Read {
+    // Read one byte at a time straight from fd 0 so nothing past the line is
+    // consumed, the rest belongs to whoever reads stdin next
+    fn read_line(raw: bool) -> Option<String> {
+        let mut line = Vec::new();
+        let mut escaped = false;
+
+        loop {
+            let mut byte = 0u8;
+            let n = unsafe { libc::read(0, (&mut byte as *mut u8).cast(), 1) };
+            if n <= 0 {
+                // EOF, a partial last line still counts
+                return (!line.is_empty()).then(|| String::from_utf8_lossy(&line).into_owned());
+            }
+
+            match (byte, escaped) {
+                // Backslash-newline continues the line
+                (b'\n', true) => escaped = false,
+                (b'\n', false) => break,
+                (b'\\', false) if !raw => escaped = true,
+                (b, _) => {
+                    line.push(b);
+                    escaped = false;
+                }
+            }
+        }
+
+        Some(String::from_utf8_lossy(&line).into_owned())
+    }
+
+    // Assign fields to names, the last name takes the rest of the line
+    fn assign(
+        line: &str,
+        names: &[String],
+        ifs: &str,
+        ctx: &mut ShellContext,
+    ) -> Result<(), String> {
+        let is_ifs = |c: char| ifs.contains(c);
+        let mut rest = line.trim_start_matches(|c: char| is_ifs(c) && c.is_ascii_whitespace());
+
+        for (i, name) in names.iter().enumerate() {
+            if i == names.len() - 1 {
+                let value = rest.trim_end_matches(|c: char| is_ifs(c) && c.is_ascii_whitespace());
+                return ctx.assign(name, value);
+            }
+
+            let end = rest.find(is_ifs).unwrap_or(rest.len());
+            ctx.assign(name, &rest[..end])?;
+            rest = rest[end..].trim_start_matches(|c: char| is_ifs(c) && c.is_ascii_whitespace());
+            // A single non-whitespace delimiter separates one field
+            if let Some(stripped) =
+                rest.strip_prefix(|c: char| is_ifs(c) && !c.is_ascii_whitespace())
+            {
+                rest = stripped.trim_start_matches(|c: char| is_ifs(c) && c.is_ascii_whitespace());
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl BuiltinCommand for Read {
+    fn name(&self) -> &str {
+        "read"
+    }
+    fn execute(&self, args: &[String], ctx: &mut ShellContext) -> i32 {
+        let mut raw = false;
+        let mut names = Vec::new();
+        let mut it = args.iter();
+
+        while let Some(arg) = it.next() {
+            match arg.as_str() {
+                "-r" => raw = true,
+                "-p" => {
+                    let Some(prompt) = it.next() else {
+                        eprintln!("{}: -p: option requires an argument", self.name());
+                        return 2;
+                    };
+                    eprint!("{prompt}");
+                    let _ = io::stderr().flush();
+                }
+                _ => names.push(arg.clone()),
+            }
+        }
+
+        // Fails at end of input so `while read line` loops terminate
+        let Some(line) = Self::read_line(raw) else {
+            return 1;
+        };
+
+        let assigned = if names.is_empty() {
+            ctx.assign("REPLY", &line)
+        } else {
+            let ifs = ctx.env("IFS").unwrap_or(" \t\n").to_string();
+            Self::assign(&line, &names, &ifs, ctx)
+        };
+
+        match assigned {
+            Ok(()) => 0,
+            Err(e) => {
+                eprintln!("{}: {e}", self.name());
+                1
+            }
+        }
+    }
+}
@@ -0,0 +1,67 @@
+use super::export::{assign_arg, print_declarations};
+use super::{BuiltinCommand, ShellContext};
+
+pub struct Readonly;
+
+impl BuiltinCommand for Readonly {
+    fn name(&self) -> &str {
+        "readonly"
+    }
+    fn execute(&self, args: &[String], ctx: &mut ShellContext) -> i32 {
+        let names = match args.first().map(String::as_str) {
+            Some("-p") | Some("--") => &args[1..],
+            Some(flag) if flag.starts_with('-') && flag.len() > 1 => {
+                eprintln!("{}: {flag}: invalid option", self.name());
+                return 2;
+            }
+            _ => args,
+        };
+
+        if names.is_empty() || args[0] == "-p" {
+            print_declarations(ctx, |var| var.readonly);
+            return 0;
+        }
+
+        let mut status = 0;
+        for arg in names {
+            match assign_arg(arg, ctx) {
+                Ok(name) => ctx.set_readonly(name),
+                Err(e) => {
+                    eprintln!("{}: {e}", self.name());
+                    status = 1;
+                }
+            }
+        }
+
+        status
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::job::List;
+
+    #[test]
+    fn test_readonly() {
+        let mut ctx = ShellContext::new();
+        List::run_source("R=1; readonly R S=2", &mut ctx);
+        assert_eq!(0, ctx.status());
+        assert!(ctx.is_readonly("R") && ctx.is_readonly("S"));
+
+        for source in [
+            "R=3",
+            "R=3 true",
+            "readonly R=3",
+            "for R in 3; do true; done",
+        ] {
+            List::run_source(source, &mut ctx);
+            assert_eq!(1, ctx.status(), "{source}");
+        }
+        List::run_source("((S += 1))", &mut ctx);
+        assert_ne!(0, ctx.status());
+        List::run_source("readonly PWD; cd .", &mut ctx);
+        assert_eq!(1, ctx.status());
+        assert_eq!((Some("1"), Some("2")), (ctx.env("R"), ctx.env("S")));
+    }
+}
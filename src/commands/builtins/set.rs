@@ -0,0 +1,95 @@
+use super::{BuiltinCommand, ShellContext};
+use crate::context::ShellOption;
+
+pub struct Set;
+
+impl Set {
+    fn parse_set_args(args: &[String]) -> Result<SetAction, String> {
+        let mut it = args.iter();
+
+        let Some(flag) = it.next() else {
+            return Ok(SetAction::List { reusable: false });
+        };
+        let enabled = match flag.as_str() {
+            "-o" => true,
+            "+o" => false,
+            _ => return Err(format!("set: {flag}: invalid option")),
+        };
+
+        let Some(name) = it.next() else {
+            return Ok(SetAction::List { reusable: !enabled });
+        };
+        let option = ShellOption::from_name(&ShellOption::SET, name)
+            .ok_or_else(|| format!("set: {name}: invalid option name"))?;
+
+        match it.next() {
+            Some(_) => Err("set: too many arguments".into()),
+            None => Ok(SetAction::Set { option, enabled }),
+        }
+    }
+}
+
+impl BuiltinCommand for Set {
+    fn name(&self) -> &str {
+        "set"
+    }
+    fn execute(&self, args: &[String], ctx: &mut ShellContext) -> i32 {
+        let action = match Self::parse_set_args(args) {
+            Ok(a) => a,
+            Err(e) => {
+                eprintln!("{e}");
+                return 2;
+            }
+        };
+
+        match action {
+            SetAction::Set { option, enabled } => ctx.set_option(option, enabled),
+            SetAction::List { reusable } => {
+                for option in ShellOption::SET {
+                    let enabled = ctx.option(option);
+                    if reusable {
+                        let flag = if enabled { "-o" } else { "+o" };
+                        println!("set {flag} {}", option.name());
+                    } else {
+                        let state = if enabled { "on" } else { "off" };
+                        println!("{:<15}\t{state}", option.name());
+                    }
+                }
+            }
+        }
+
+        0
+    }
+}
+
+enum SetAction {
+    // `set -o` lists options, `set +o` as commands that recreate them
+    List { reusable: bool },
+    Set { option: ShellOption, enabled: bool },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse() {
+        assert!(matches!(
+            Set::parse_set_args(&args(&["-o", "pipefail"])),
+            Ok(SetAction::Set {
+                option: ShellOption::Pipefail,
+                enabled: true
+            })
+        ));
+        assert!(matches!(
+            Set::parse_set_args(&args(&["+o"])),
+            Ok(SetAction::List { reusable: true })
+        ));
+        assert!(Set::parse_set_args(&args(&["-o", "dotglob"])).is_err());
+        assert!(Set::parse_set_args(&args(&["-x"])).is_err());
+    }
+}
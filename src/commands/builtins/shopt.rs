@@ -0,0 +1,68 @@
+use super::{BuiltinCommand, ShellContext};
+use crate::context::ShellOption;
+
+pub struct Shopt;
+
+impl Shopt {
+    fn parse_shopt_args(args: &[String]) -> Result<(ShoptAction, Vec<ShellOption>), String> {
+        let mut action = ShoptAction::Print;
+        let mut options = Vec::new();
+
+        for arg in args {
+            match arg.as_str() {
+                "-s" => action = ShoptAction::Set(true),
+                "-u" => action = ShoptAction::Set(false),
+                "-p" => {}
+                _ if arg.starts_with('-') => return Err(format!("shopt: {arg}: invalid option")),
+                _ => match ShellOption::from_name(&ShellOption::SHOPT, arg) {
+                    Some(option) => options.push(option),
+                    None => return Err(format!("shopt: {arg}: invalid shell option name")),
+                },
+            }
+        }
+
+        Ok((action, options))
+    }
+}
+
+impl BuiltinCommand for Shopt {
+    fn name(&self) -> &str {
+        "shopt"
+    }
+    fn execute(&self, args: &[String], ctx: &mut ShellContext) -> i32 {
+        let (action, mut options) = match Self::parse_shopt_args(args) {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                eprintln!("{e}");
+                return 2;
+            }
+        };
+
+        match action {
+            ShoptAction::Set(enabled) => {
+                for option in options {
+                    ctx.set_option(option, enabled);
+                }
+                0
+            }
+            ShoptAction::Print => {
+                // Querying named options fails if any of them is off
+                let all_set = options.iter().all(|o| ctx.option(*o));
+                if options.is_empty() {
+                    options = ShellOption::SHOPT.to_vec();
+                }
+
+                for option in options {
+                    let flag = if ctx.option(option) { "-s" } else { "-u" };
+                    println!("shopt {flag} {}", option.name());
+                }
+                (!all_set) as i32
+            }
+        }
+    }
+}
+
+enum ShoptAction {
+    Print,
+    Set(bool),
+}
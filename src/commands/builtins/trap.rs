@@ -0,0 +1,155 @@
+use super::{BuiltinCommand, ShellContext};
+use crate::trap::Condition;
+
+pub struct Trap;
+
+impl Trap {
+    fn parse_trap_args(args: &[String]) -> Result<TrapAction, TrapError> {
+        let mut args = args;
+        let mut print = false;
+
+        while let Some(arg) = args.first() {
+            match arg.as_str() {
+                "-p" => print = true,
+                "--" => {
+                    args = &args[1..];
+                    break;
+                }
+                flag if flag.starts_with('-') && flag.len() > 1 => {
+                    return Err(TrapError::Usage(format!("trap: {flag}: invalid option")));
+                }
+                _ => break,
+            }
+            args = &args[1..];
+        }
+
+        let conditions = |specs: &[String]| {
+            specs
+                .iter()
+                .map(|spec| {
+                    Condition::parse(spec).ok_or_else(|| {
+                        TrapError::Invalid(format!("trap: {spec}: invalid signal specification"))
+                    })
+                })
+                .collect::<Result<Vec<_>, _>>()
+        };
+
+        match args {
+            _ if print || args.is_empty() => Ok(TrapAction::Print(conditions(args)?)),
+            // A lone condition is reset
+            [_] => Ok(TrapAction::Set {
+                action: None,
+                conditions: conditions(args)?,
+            }),
+            [action, specs @ ..] => Ok(TrapAction::Set {
+                action: (action != "-").then(|| action.clone()),
+                conditions: conditions(specs)?,
+            }),
+            [] => unreachable!(),
+        }
+    }
+}
+
+impl BuiltinCommand for Trap {
+    fn name(&self) -> &str {
+        "trap"
+    }
+    fn execute(&self, args: &[String], ctx: &mut ShellContext) -> i32 {
+        let action = match Self::parse_trap_args(args) {
+            Ok(a) => a,
+            Err(TrapError::Usage(e)) => {
+                eprintln!("{e}");
+                return 2;
+            }
+            Err(TrapError::Invalid(e)) => {
+                eprintln!("{e}");
+                return 1;
+            }
+        };
+
+        match action {
+            TrapAction::Print(conditions) => {
+                for (condition, action) in ctx.traps().iter() {
+                    if conditions.is_empty() || conditions.contains(&condition) {
+                        let action = action.replace('\'', r"'\''");
+                        println!("trap -- '{action}' {}", condition.name());
+                    }
+                }
+            }
+            TrapAction::Set { action, conditions } => {
+                for condition in conditions {
+                    if let Err(e) = ctx.traps_mut().set(condition, action.as_deref()) {
+                        eprintln!("{}: {}: {e}", self.name(), condition.name());
+                        return 1;
+                    }
+                }
+            }
+        }
+
+        0
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum TrapAction {
+    // `trap` or `trap -p [condition...]`, every trap when none are named
+    Print(Vec<Condition>),
+    // `trap action condition...`, `-` as the action restores the default
+    Set {
+        action: Option<String>,
+        conditions: Vec<Condition>,
+    },
+}
+
+#[derive(Debug, PartialEq)]
+enum TrapError {
+    // An option that isn't known, which is a usage error
+    Usage(String),
+    // A condition that names no signal, nothing is set then
+    Invalid(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libc::{SIGINT, SIGTERM};
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            Ok(TrapAction::Set {
+                action: Some("rm -f $tmp".to_string()),
+                conditions: vec![Condition::Exit, Condition::Signal(SIGINT)],
+            }),
+            Trap::parse_trap_args(&args(&["rm -f $tmp", "EXIT", "INT"]))
+        );
+        assert_eq!(
+            Ok(TrapAction::Set {
+                action: None,
+                conditions: vec![Condition::Signal(SIGTERM)],
+            }),
+            Trap::parse_trap_args(&args(&["-", "TERM"]))
+        );
+        assert_eq!(
+            Trap::parse_trap_args(&args(&["-", "TERM"])),
+            Trap::parse_trap_args(&args(&["TERM"]))
+        );
+        assert_eq!(
+            Ok(TrapAction::Print(vec![Condition::Err])),
+            Trap::parse_trap_args(&args(&["-p", "ERR"]))
+        );
+        assert_eq!(Ok(TrapAction::Print(vec![])), Trap::parse_trap_args(&[]));
+        assert!(matches!(
+            Trap::parse_trap_args(&args(&["x", "NOPE"])),
+            Err(TrapError::Invalid(_))
+        ));
+        assert!(matches!(
+            Trap::parse_trap_args(&args(&["-x"])),
+            Err(TrapError::Usage(_))
+        ));
+    }
+}
@@ -7,16 +7,18 @@ impl BuiltinCommand for Type {
     fn name(&self) -> &str {
         "type"
     }
-    fn execute(&self, args: &[String], ctx: &mut ShellContext) {
+    fn execute(&self, args: &[String], ctx: &mut ShellContext) -> i32 {
         for arg in args {
             if ctx.registry().check_builtin(arg) {
                 println!("{} is a shell builtin", arg)
             } else {
                 let Some(path) = ExtCommand::search_binary(arg, ctx) else {
-                    return;
+                    return 1;
                 };
                 println!("{arg} is {}", path.to_str().unwrap())
             }
         }
+
+        0
     }
 }
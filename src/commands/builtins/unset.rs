@@ -0,0 +1,62 @@
+use super::{BuiltinCommand, ShellContext};
+use crate::expand::is_name;
+
+pub struct Unset;
+
+impl BuiltinCommand for Unset {
+    fn name(&self) -> &str {
+        "unset"
+    }
+    fn execute(&self, args: &[String], ctx: &mut ShellContext) -> i32 {
+        // There are no shell functions to unset with `-f`
+        let (functions, names) = match args.first().map(String::as_str) {
+            Some("-f") => (true, &args[1..]),
+            Some("-v") | Some("--") => (false, &args[1..]),
+            Some(flag) if flag.starts_with('-') && flag.len() > 1 => {
+                eprintln!("{}: {flag}: invalid option", self.name());
+                return 2;
+            }
+            _ => (false, args),
+        };
+        if functions {
+            return 0;
+        }
+
+        let mut status = 0;
+        for name in names {
+            if !is_name(name) {
+                eprintln!("{}: `{name}': not a valid identifier", self.name());
+                status = 1;
+            } else if ctx.is_readonly(name) {
+                eprintln!("{}: {name}: cannot unset: readonly variable", self.name());
+                status = 1;
+            } else {
+                ctx.unset_env(name);
+            }
+        }
+
+        status
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::job::List;
+
+    #[test]
+    fn test_unset() {
+        let mut ctx = ShellContext::new();
+        List::run_source(
+            "A=1 B=2 R=3; export A; readonly R; unset -v A B R",
+            &mut ctx,
+        );
+        assert_eq!(1, ctx.status());
+        assert_eq!((None, None), (ctx.env("A"), ctx.env("B")));
+        assert_eq!(Some("3"), ctx.env("R"));
+
+        // Exported again only when asked to
+        List::run_source("A=4", &mut ctx);
+        assert!(!ctx.exported().any(|(name, _)| name == "A"));
+    }
+}
@@ -0,0 +1,152 @@
+use super::{BuiltinCommand, ShellContext};
+use crate::signal::{self, CatchGuard};
+use crate::trap::{self, Condition};
+
+use libc::{SIGCHLD, SIGINT};
+
+pub struct Wait;
+
+impl Wait {
+    // Whether `-n` was given and the job specs or pids to wait for
+    fn parse_wait_args(args: &[String]) -> Result<(bool, &[String]), String> {
+        match args.first().map(String::as_str) {
+            Some("-n") => Ok((true, &args[1..])),
+            Some("--") => Ok((false, &args[1..])),
+            Some(flag) if flag.starts_with('-') && flag.parse::<i32>().is_err() => {
+                Err(format!("wait: {flag}: invalid option"))
+            }
+            _ => Ok((false, args)),
+        }
+    }
+
+    // The job number a job spec or pid names
+    fn find(target: &str, ctx: &ShellContext) -> Result<usize, String> {
+        if target.starts_with('%') {
+            return ctx.jobs().find(target);
+        }
+
+        match target.parse::<i32>() {
+            Ok(pid) => ctx
+                .jobs()
+                .find_pid(pid)
+                .ok_or_else(|| format!("pid {pid} is not a child of this shell")),
+            Err(_) => Err(format!("`{target}': not a pid or valid job spec")),
+        }
+    }
+}
+
+impl BuiltinCommand for Wait {
+    fn name(&self) -> &str {
+        "wait"
+    }
+    fn execute(&self, args: &[String], ctx: &mut ShellContext) -> i32 {
+        let (any, targets) = match Self::parse_wait_args(args) {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                eprintln!("{e}");
+                return 2;
+            }
+        };
+
+        // Without arguments every job is waited for
+        let found: Vec<_> = targets
+            .iter()
+            .map(|target| Self::find(target, ctx))
+            .inspect(|found| {
+                if let Err(e) = found {
+                    eprintln!("{}: {e}", self.name());
+                }
+            })
+            .collect();
+        let ids: Vec<usize> = match targets.is_empty() {
+            true => ctx.jobs().iter().map(|job| job.id).collect(),
+            false => found.iter().flatten().copied().collect(),
+        };
+        if ids.is_empty() {
+            return if any || !targets.is_empty() { 127 } else { 0 };
+        }
+
+        // A child exiting wakes the loop up, and so does a signal with a
+        // trap or an interrupt typed at the terminal
+        let interrupt: &[_] = match ctx.job_control() {
+            true => &[SIGCHLD, SIGINT],
+            false => &[SIGCHLD],
+        };
+        let _catch = match CatchGuard::new(interrupt) {
+            Ok(guard) => guard,
+            Err(e) => {
+                eprintln!("{}: {e}", self.name());
+                return 1;
+            }
+        };
+
+        let done = loop {
+            ctx.jobs_mut().update();
+            let done: Vec<(usize, i32)> = ids
+                .iter()
+                .filter_map(|id| {
+                    let job = ctx.jobs().iter().find(|job| job.id == *id)?;
+                    Some((*id, job.state.status()?))
+                })
+                .collect();
+            if (any && !done.is_empty()) || done.len() == ids.len() {
+                break done;
+            }
+
+            // Stops waiting with the status a signal would have killed it with
+            let caught = signal::wait_pending();
+            if let Some(&sig) = caught.iter().find(|sig| **sig != SIGCHLD) {
+                for sig in caught {
+                    trap::run(Condition::Signal(sig), ctx);
+                }
+                return 128 + sig;
+            }
+        };
+
+        // Jobs waited for aren't reported as done later
+        for (id, _) in &done {
+            let finished = ctx
+                .jobs()
+                .iter()
+                .any(|job| job.id == *id && job.state.is_finished());
+            if finished {
+                ctx.jobs_mut().remove(*id);
+            }
+        }
+
+        // The status of the job that finished first with `-n`, otherwise of
+        // the last one named
+        let status_of = |id: &usize| done.iter().find(|(other, _)| other == id).map(|(_, s)| *s);
+        match (any, found.last()) {
+            (true, _) => done[0].1,
+            (false, None) => 0,
+            (false, Some(Ok(last))) => status_of(last).unwrap_or(127),
+            (false, Some(Err(_))) => 127,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::job::List;
+
+    #[test]
+    fn test_wait() {
+        let mut ctx = ShellContext::new();
+
+        List::run_source("exit 3 & exit 4 & wait %1", &mut ctx);
+        assert_eq!(3, ctx.status());
+        List::run_source("wait $!", &mut ctx);
+        assert_eq!(4, ctx.status());
+        assert_eq!(0, ctx.jobs().iter().count());
+
+        List::run_source("exit 5 & wait -n", &mut ctx);
+        assert_eq!(5, ctx.status());
+        List::run_source("wait -n", &mut ctx);
+        assert_eq!(127, ctx.status());
+
+        List::run_source("wait %1 nope", &mut ctx);
+        assert_eq!(127, ctx.status());
+    }
+}
@@ -12,10 +12,11 @@ impl ExtCommand {
         for path in ctx.paths() {
             let bin_path = path.join(name);
 
-            if let Ok(meta) = std::fs::metadata(&bin_path) {
-                if meta.is_file() && (meta.permissions().mode() & 0o001 != 0) {
-                    return Some(bin_path);
-                }
+            if let Ok(meta) = std::fs::metadata(&bin_path)
+                && meta.is_file()
+                && (meta.permissions().mode() & 0o001 != 0)
+            {
+                return Some(bin_path);
             }
         }
 
@@ -23,11 +24,27 @@ impl ExtCommand {
         None
     }
 
-    pub fn execute(name: &str, args: &[String], ctx: &ShellContext) {
+    /// Replace the current process with `name`, with `env` added to the
+    /// exported variables as its environment, only returning the exit status
+    /// to use if that fails.
+    pub fn execute(
+        name: &str,
+        args: &[String],
+        env: &[(String, String)],
+        ctx: &ShellContext,
+    ) -> i32 {
         let Some(path) = ExtCommand::search_binary(name, ctx) else {
-            return;
+            return 127;
         };
 
-        let _ = Command::new(path).arg0(name).args(args).exec();
+        let e = Command::new(path)
+            .arg0(name)
+            .args(args)
+            .env_clear()
+            .envs(ctx.exported())
+            .envs(env.iter().map(|(k, v)| (k, v)))
+            .exec();
+        eprintln!("{name}: {e}");
+        126
     }
 }
@@ -1,6 +1,8 @@
 use crate::commands::{Registry, init_registry};
+use crate::job::JobTable;
+use crate::trap::{self, Condition, Traps};
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     env,
     fs::File,
     io::{self, BufRead, BufReader, BufWriter, Write},
@@ -11,7 +13,57 @@ pub struct ShellContext {
     registry: Registry,
     env: Env,
     cwd: PathBuf,
+    // `$$`, the shell's own process even in a subshell
+    pid: u32,
     history: History,
+    options: HashSet<ShellOption>,
+    // Exit status of the last pipeline, `$?`
+    status: i32,
+    // Status of each stage of the last pipeline, `PIPESTATUS`
+    pipestatus: Vec<i32>,
+    jobs: JobTable,
+    // Whether jobs get process groups of their own and the terminal
+    job_control: bool,
+    // Whether this is a child forked to run part of a command
+    subshell: bool,
+    traps: Traps,
+    // Status of the last command substitution, what a command without a
+    // name exits with
+    substitution_status: Option<i32>,
+}
+
+/// Options toggled with `shopt` or `set -o`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ShellOption {
+    Dotglob,
+    Failglob,
+    Nullglob,
+    Pipefail,
+}
+
+impl ShellOption {
+    /// Options managed by `shopt`.
+    pub const SHOPT: [ShellOption; 3] = [
+        ShellOption::Dotglob,
+        ShellOption::Failglob,
+        ShellOption::Nullglob,
+    ];
+
+    /// Options managed by `set -o`.
+    pub const SET: [ShellOption; 1] = [ShellOption::Pipefail];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ShellOption::Dotglob => "dotglob",
+            ShellOption::Failglob => "failglob",
+            ShellOption::Nullglob => "nullglob",
+            ShellOption::Pipefail => "pipefail",
+        }
+    }
+
+    pub fn from_name(options: &[ShellOption], name: &str) -> Option<ShellOption> {
+        options.iter().copied().find(|o| o.name() == name)
+    }
 }
 
 impl ShellContext {
@@ -20,7 +72,16 @@ impl ShellContext {
             registry: init_registry(),
             env: Env::from_system(),
             cwd: env::current_dir().expect("couldn't get cwd"),
+            pid: std::process::id(),
             history: History::new(),
+            options: HashSet::new(),
+            status: 0,
+            pipestatus: vec![0],
+            jobs: JobTable::default(),
+            job_control: false,
+            subshell: false,
+            traps: Traps::default(),
+            substitution_status: None,
         }
     }
 
@@ -44,6 +105,56 @@ impl ShellContext {
         self.env.unset(key)
     }
 
+    /// Set a variable the way a command does, failing when it's readonly.
+    pub fn assign(&mut self, key: &str, value: &str) -> Result<(), String> {
+        if self.is_readonly(key) {
+            return Err(format!("{key}: readonly variable"));
+        }
+
+        self.env.set(key, value);
+        Ok(())
+    }
+
+    pub fn is_readonly(&self, key: &str) -> bool {
+        self.env.vars.get(key).is_some_and(|var| var.readonly)
+    }
+
+    /// Mark a variable as readonly, even one that isn't set yet.
+    pub fn set_readonly(&mut self, key: &str) {
+        self.env.vars.entry(key.to_string()).or_default().readonly = true;
+    }
+
+    /// Add a variable to the environment of commands, or with `exported`
+    /// unset keep it in the shell only.
+    pub fn set_exported(&mut self, key: &str, exported: bool) {
+        match self.env.vars.get_mut(key) {
+            Some(var) => var.exported = exported,
+            None if exported => {
+                let var = Var {
+                    exported,
+                    ..Var::default()
+                };
+                self.env.vars.insert(key.to_string(), var);
+            }
+            None => {}
+        }
+    }
+
+    /// Every variable with its attributes, including those marked without a
+    /// value.
+    pub fn vars(&self) -> impl Iterator<Item = (&str, &Var)> {
+        self.env.vars.iter().map(|(key, var)| (key.as_str(), var))
+    }
+
+    /// The environment commands are run with: every exported variable that
+    /// has a value.
+    pub fn exported(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.vars().filter_map(|(key, var)| match var.exported {
+            true => Some((key, var.value.as_deref()?)),
+            false => None,
+        })
+    }
+
     pub fn paths(&self) -> impl Iterator<Item = PathBuf> {
         self.env
             .get("PATH")
@@ -52,6 +163,10 @@ impl ShellContext {
             .map(PathBuf::from)
     }
 
+    pub fn pid(&self) -> u32 {
+        self.pid
+    }
+
     pub fn cwd(&self) -> &Path {
         &self.cwd
     }
@@ -60,6 +175,75 @@ impl ShellContext {
         self.cwd = PathBuf::from(path);
     }
 
+    pub fn option(&self, option: ShellOption) -> bool {
+        self.options.contains(&option)
+    }
+
+    pub fn set_option(&mut self, option: ShellOption, enabled: bool) {
+        if enabled {
+            self.options.insert(option);
+        } else {
+            self.options.remove(&option);
+        }
+    }
+
+    pub fn status(&self) -> i32 {
+        self.status
+    }
+
+    pub fn set_status(&mut self, status: i32) {
+        self.status = status;
+    }
+
+    pub fn pipestatus(&self) -> &[i32] {
+        &self.pipestatus
+    }
+
+    pub fn set_pipestatus(&mut self, statuses: Vec<i32>) {
+        self.pipestatus = statuses;
+    }
+
+    pub fn jobs(&self) -> &JobTable {
+        &self.jobs
+    }
+
+    pub fn jobs_mut(&mut self) -> &mut JobTable {
+        &mut self.jobs
+    }
+
+    pub fn job_control(&self) -> bool {
+        self.job_control
+    }
+
+    pub fn set_job_control(&mut self, enabled: bool) {
+        self.job_control = enabled;
+    }
+
+    pub fn traps(&self) -> &Traps {
+        &self.traps
+    }
+
+    pub fn traps_mut(&mut self) -> &mut Traps {
+        &mut self.traps
+    }
+
+    /// Drop what only the shell itself does, in a child forked to run part of
+    /// a command: job control, traps other than ignored signals, and the
+    /// cleanup done on exit.
+    pub fn enter_subshell(&mut self) {
+        self.job_control = false;
+        self.subshell = true;
+        self.traps.reset_for_subshell();
+    }
+
+    pub fn set_substitution_status(&mut self, status: i32) {
+        self.substitution_status = Some(status);
+    }
+
+    pub fn take_substitution_status(&mut self) -> Option<i32> {
+        self.substitution_status.take()
+    }
+
     pub fn historys(&self) -> &[String] {
         &self.history.entries
     }
@@ -98,6 +282,18 @@ impl ShellContext {
     }
 
     pub fn shutdown(&mut self) {
+        trap::run(Condition::Exit, self);
+
+        // The history and jobs belong to the shell, not its copy
+        if self.subshell {
+            return;
+        }
+
+        // Jobs of an interactive shell go away with it
+        if self.job_control {
+            self.jobs.hangup();
+        }
+
         // Gracefully exit shell
         if let Some(path) = self.env("HISTFILE") {
             let _ = self.flush_history(&PathBuf::from(path));
@@ -106,30 +302,54 @@ impl ShellContext {
 }
 
 struct Env {
-    vars: HashMap<String, String>,
+    vars: HashMap<String, Var>,
+}
+
+/// A shell variable. Only exported ones are passed on to commands.
+#[derive(Debug, Default)]
+pub struct Var {
+    // None for a name given attributes before it was set, e.g. `export NAME`
+    pub value: Option<String>,
+    pub exported: bool,
+    pub readonly: bool,
 }
 
 impl Env {
     fn get(&self, key: &str) -> Option<&str> {
-        self.vars.get(key).map(String::as_str)
+        self.vars.get(key)?.value.as_deref()
     }
 
-    // Replace the old value
+    // Replace the old value, a new variable stays local to the shell
     fn set(&mut self, key: &str, value: &str) -> Option<String> {
-        self.vars.insert(key.to_string(), value.to_string())
+        let var = self.vars.entry(key.to_string()).or_default();
+        var.value.replace(value.to_string())
     }
 
     fn unset(&mut self, key: &str) -> Option<String> {
-        self.vars.remove(key)
+        self.vars.remove(key)?.value
     }
 
+    // Everything the shell was started with is exported again
     fn from_system() -> Env {
+        let vars = env::vars().map(|(key, value)| {
+            let var = Var {
+                value: Some(value),
+                exported: true,
+                readonly: false,
+            };
+            (key, var)
+        });
+
         Env {
-            vars: env::vars().collect(),
+            vars: vars.collect(),
         }
     }
 }
 
+// First line of a history file whose entries have newlines and backslashes
+// escaped, the format rustyline saves multi-line entries in
+const HISTORY_V2: &str = "#V2";
+
 pub struct History {
     entries: Vec<String>,
     flush_index: usize,
@@ -162,12 +382,15 @@ impl History {
 
     fn read(&mut self, target: &Path) -> io::Result<()> {
         let file = File::options().read(true).open(target)?;
-        let buf = BufReader::new(file);
+        let mut lines = BufReader::new(file).lines().peekable();
+        let escaped = lines
+            .next_if(|line| matches!(line, Ok(line) if line == HISTORY_V2))
+            .is_some();
 
-        buf.lines().try_for_each(|line| {
+        lines.try_for_each(|line| {
             let line = line?;
             if !line.is_empty() {
-                self.push(&line);
+                self.push(&Self::decode(&line, escaped));
             }
             Ok(())
         })
@@ -181,8 +404,12 @@ impl History {
             .open(target)?;
         let mut writer = BufWriter::new(file);
 
+        let escaped = self.entries.iter().any(|entry| entry.contains('\n'));
+        if escaped {
+            writeln!(writer, "{HISTORY_V2}")?;
+        }
         for entry in self.entries.iter() {
-            writeln!(writer, "{entry}")?;
+            writeln!(writer, "{}", Self::encode(entry, escaped))?;
         }
 
         writer.flush()?;
@@ -190,12 +417,34 @@ impl History {
     }
 
     fn flush(&mut self, target: &Path) -> io::Result<()> {
+        let format = Self::format_of(target)?;
+        let multiline = self
+            .unflushed_history()
+            .iter()
+            .any(|entry| entry.contains('\n'));
+
+        // A plain file is rewritten escaped before a multi-line entry goes in
+        if format == Some(false) && multiline {
+            let mut all = History::new();
+            all.read(target)?;
+            all.entries.extend_from_slice(self.unflushed_history());
+            all.write(target)?;
+
+            self.mark_flushed();
+            return Ok(());
+        }
+
         let file = File::options().create(true).append(true).open(target)?;
         let mut writer = BufWriter::new(file);
 
+        let escaped = format.unwrap_or(multiline);
+        if format.is_none() && escaped {
+            writeln!(writer, "{HISTORY_V2}")?;
+        }
+
         // Append only new entry
         for entry in self.unflushed_history() {
-            writeln!(writer, "{entry}")?;
+            writeln!(writer, "{}", Self::encode(entry, escaped))?;
         }
 
         writer.flush()?;
@@ -205,4 +454,66 @@ impl History {
 
         Ok(())
     }
+
+    // Whether `target` holds escaped entries, `None` when it's missing or
+    // empty and either format will do
+    fn format_of(target: &Path) -> io::Result<Option<bool>> {
+        let file = match File::open(target) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+
+        match BufReader::new(file).lines().next().transpose()? {
+            Some(first) => Ok(Some(first == HISTORY_V2)),
+            None => Ok(None),
+        }
+    }
+
+    fn encode(entry: &str, escaped: bool) -> String {
+        match escaped {
+            true => entry.replace('\\', r"\\").replace('\n', r"\n"),
+            false => entry.to_string(),
+        }
+    }
+
+    fn decode(line: &str, escaped: bool) -> String {
+        if !escaped {
+            return line.to_string();
+        }
+
+        let mut entry = String::with_capacity(line.len());
+        let mut chars = line.chars().peekable();
+        while let Some(ch) = chars.next() {
+            if ch != '\\' {
+                entry.push(ch);
+                continue;
+            }
+
+            match chars.next_if(|next| matches!(next, 'n' | '\\')) {
+                Some('n') => entry.push('\n'),
+                _ => entry.push('\\'),
+            }
+        }
+
+        entry
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_history_escape() {
+        for entry in ["ls", r"echo a\b\\", "for i in 1\ndo echo \\n$i\ndone"] {
+            let line = History::encode(entry, true);
+            assert!(!line.contains('\n'), "{line:?}");
+            assert_eq!(entry, History::decode(&line, true));
+        }
+
+        // Files without multi-line entries stay plain
+        assert_eq!(r"echo a\b", History::encode(r"echo a\b", false));
+        assert_eq!(r"echo a\nb", History::decode(r"echo a\nb", false));
+    }
 }
@@ -0,0 +1,480 @@
+use super::ExpandError;
+use crate::context::ShellContext;
+
+// Guard against variables that refer back to themselves, e.g. `a=a`
+const MAX_DEPTH: usize = 32;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Num(i64),
+    Name(String),
+    Op(&'static str),
+}
+
+#[derive(Debug)]
+enum Expr {
+    Num(i64),
+    Var(String),
+    Unary(&'static str, Box<Expr>),
+    Binary(&'static str, Box<Expr>, Box<Expr>),
+    Ternary(Box<Expr>, Box<Expr>, Box<Expr>),
+    // `op` is `=` or a compound operator such as `+=`
+    Assign(&'static str, String, Box<Expr>),
+    // `++x` / `--x` and `x++` / `x--`
+    PreStep(String, i64),
+    PostStep(String, i64),
+}
+
+// Longest operators first so `<<=` wins over `<<` and `<`
+const OPERATORS: &[&str] = &[
+    "<<=", ">>=", "**", "++", "--", "<<", ">>", "<=", ">=", "==", "!=", "&&", "||", "+=", "-=",
+    "*=", "/=", "%=", "&=", "^=", "|=", "+", "-", "*", "/", "%", "<", ">", "&", "^", "|", "!", "~",
+    "?", ":", "=", "(", ")", ",",
+];
+
+/// Evaluate an arithmetic expression, reading and assigning shell variables.
+pub fn evaluate(text: &str, ctx: &mut ShellContext) -> Result<i64, ExpandError> {
+    evaluate_at(text, ctx, 0)
+}
+
+fn evaluate_at(text: &str, ctx: &mut ShellContext, depth: usize) -> Result<i64, ExpandError> {
+    let error = |message: String| ExpandError::Arith {
+        expr: text.trim().to_string(),
+        message,
+    };
+
+    if depth > MAX_DEPTH {
+        return Err(error("expression recursion level exceeded".to_string()));
+    }
+
+    let tokens = tokenize(text).map_err(error)?;
+    if tokens.is_empty() {
+        return Ok(0);
+    }
+
+    let mut parser = Parser { tokens, pos: 0 };
+    let expr = parser.parse_comma().map_err(error)?;
+    if let Some(token) = parser.peek() {
+        return Err(error(format!(
+            "syntax error in expression (error token is \"{}\")",
+            token_text(token)
+        )));
+    }
+
+    Evaluator { ctx, depth }.eval(&expr).map_err(|e| match e {
+        EvalError::Message(message) => error(message),
+        EvalError::Nested(e) => e,
+    })
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = text;
+
+    while let Some(ch) = rest.chars().next() {
+        if ch.is_whitespace() {
+            rest = &rest[ch.len_utf8()..];
+        } else if ch.is_ascii_digit() {
+            let len = rest
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '#' && c != '_' && c != '@')
+                .unwrap_or(rest.len());
+            tokens.push(Token::Num(parse_number(&rest[..len])?));
+            rest = &rest[len..];
+        } else if ch == '_' || ch.is_ascii_alphabetic() {
+            let len = rest
+                .find(|c: char| c != '_' && !c.is_ascii_alphanumeric())
+                .unwrap_or(rest.len());
+            tokens.push(Token::Name(rest[..len].to_string()));
+            rest = &rest[len..];
+        } else {
+            let Some(op) = OPERATORS.iter().find(|op| rest.starts_with(**op)) else {
+                return Err(format!(
+                    "syntax error: invalid arithmetic operator (error token is \"{rest}\")"
+                ));
+            };
+            tokens.push(Token::Op(op));
+            rest = &rest[op.len()..];
+        }
+    }
+
+    Ok(tokens)
+}
+
+// Decimal, `0x` hex, leading-zero octal and `base#digits`
+fn parse_number(text: &str) -> Result<i64, String> {
+    let invalid = || format!("{text}: value too great for base (error token is \"{text}\")");
+
+    let (base, digits) = if let Some((base, digits)) = text.split_once('#') {
+        let base: u32 = base.parse().map_err(|_| invalid())?;
+        if !(2..=64).contains(&base) {
+            return Err(format!("{text}: invalid arithmetic base"));
+        }
+        (base, digits)
+    } else if let Some(hex) = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        (16, hex)
+    } else if text.len() > 1 && text.starts_with('0') {
+        (8, &text[1..])
+    } else {
+        (10, text)
+    };
+
+    if digits.is_empty() {
+        return Err(invalid());
+    }
+
+    digits.chars().try_fold(0i64, |acc, c| {
+        let value = match c {
+            '0'..='9' => c as u32 - '0' as u32,
+            'a'..='z' => c as u32 - 'a' as u32 + 10,
+            // Upper case is the same as lower case up to base 36
+            'A'..='Z' if base <= 36 => c as u32 - 'A' as u32 + 10,
+            'A'..='Z' => c as u32 - 'A' as u32 + 36,
+            '@' => 62,
+            '_' => 63,
+            _ => return Err(invalid()),
+        };
+        if value >= base {
+            return Err(invalid());
+        }
+        Ok(acc.wrapping_mul(base as i64).wrapping_add(value as i64))
+    })
+}
+
+fn token_text(token: &Token) -> String {
+    match token {
+        Token::Num(n) => n.to_string(),
+        Token::Name(name) => name.clone(),
+        Token::Op(op) => op.to_string(),
+    }
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn peek_op(&self) -> Option<&'static str> {
+        match self.peek() {
+            Some(Token::Op(op)) => Some(op),
+            _ => None,
+        }
+    }
+
+    fn expect(&mut self, op: &str) -> Result<(), String> {
+        match self.peek_op() {
+            Some(found) if found == op => {
+                self.pos += 1;
+                Ok(())
+            }
+            _ => Err(format!("syntax error: `{op}' expected")),
+        }
+    }
+
+    fn parse_comma(&mut self) -> Result<Expr, String> {
+        let mut lhs = self.parse_assign()?;
+
+        while self.peek_op() == Some(",") {
+            self.pos += 1;
+            lhs = Expr::Binary(",", Box::new(lhs), Box::new(self.parse_assign()?));
+        }
+
+        Ok(lhs)
+    }
+
+    fn parse_assign(&mut self) -> Result<Expr, String> {
+        if let (Some(Token::Name(name)), Some(Token::Op(op))) =
+            (self.tokens.get(self.pos), self.tokens.get(self.pos + 1))
+            && matches!(
+                *op,
+                "=" | "+=" | "-=" | "*=" | "/=" | "%=" | "<<=" | ">>=" | "&=" | "^=" | "|="
+            )
+        {
+            let (name, op) = (name.clone(), *op);
+            self.pos += 2;
+            let value = self.parse_assign()?;
+            return Ok(Expr::Assign(op, name, Box::new(value)));
+        }
+
+        self.parse_ternary()
+    }
+
+    fn parse_ternary(&mut self) -> Result<Expr, String> {
+        let cond = self.parse_binary(0)?;
+
+        if self.peek_op() != Some("?") {
+            return Ok(cond);
+        }
+
+        self.pos += 1;
+        let then = self.parse_assign()?;
+        self.expect(":")?;
+        let otherwise = self.parse_assign()?;
+
+        Ok(Expr::Ternary(
+            Box::new(cond),
+            Box::new(then),
+            Box::new(otherwise),
+        ))
+    }
+
+    // Precedence climbing over the left-associative binary operators
+    fn parse_binary(&mut self, min_level: usize) -> Result<Expr, String> {
+        const LEVELS: &[&[&str]] = &[
+            &["||"],
+            &["&&"],
+            &["|"],
+            &["^"],
+            &["&"],
+            &["==", "!="],
+            &["<", ">", "<=", ">="],
+            &["<<", ">>"],
+            &["+", "-"],
+            &["*", "/", "%"],
+        ];
+
+        if min_level == LEVELS.len() {
+            return self.parse_power();
+        }
+
+        let mut lhs = self.parse_binary(min_level + 1)?;
+        while let Some(op) = self.peek_op()
+            && LEVELS[min_level].contains(&op)
+        {
+            self.pos += 1;
+            let rhs = self.parse_binary(min_level + 1)?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+
+        Ok(lhs)
+    }
+
+    // `**` is right associative and binds tighter than `*`
+    fn parse_power(&mut self) -> Result<Expr, String> {
+        let base = self.parse_unary()?;
+
+        if self.peek_op() == Some("**") {
+            self.pos += 1;
+            let exponent = self.parse_power()?;
+            return Ok(Expr::Binary("**", Box::new(base), Box::new(exponent)));
+        }
+
+        Ok(base)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr, String> {
+        match self.peek_op() {
+            Some(op @ ("++" | "--")) => {
+                self.pos += 1;
+                let Some(Token::Name(name)) = self.peek().cloned() else {
+                    return Err(format!("syntax error: operand expected after `{op}'"));
+                };
+                self.pos += 1;
+                Ok(Expr::PreStep(name, if op == "++" { 1 } else { -1 }))
+            }
+            Some(op @ ("!" | "~" | "+" | "-")) => {
+                self.pos += 1;
+                Ok(Expr::Unary(op, Box::new(self.parse_unary()?)))
+            }
+            _ => self.parse_postfix(),
+        }
+    }
+
+    fn parse_postfix(&mut self) -> Result<Expr, String> {
+        match self.peek().cloned() {
+            Some(Token::Num(n)) => {
+                self.pos += 1;
+                Ok(Expr::Num(n))
+            }
+            Some(Token::Name(name)) => {
+                self.pos += 1;
+                match self.peek_op() {
+                    Some(op @ ("++" | "--")) => {
+                        self.pos += 1;
+                        Ok(Expr::PostStep(name, if op == "++" { 1 } else { -1 }))
+                    }
+                    _ => Ok(Expr::Var(name)),
+                }
+            }
+            Some(Token::Op("(")) => {
+                self.pos += 1;
+                let expr = self.parse_comma()?;
+                self.expect(")")?;
+                Ok(expr)
+            }
+            Some(token) => Err(format!(
+                "syntax error: operand expected (error token is \"{}\")",
+                token_text(&token)
+            )),
+            None => Err("syntax error: operand expected".to_string()),
+        }
+    }
+}
+
+enum EvalError {
+    Message(String),
+    // An error from evaluating a variable's value, already formatted
+    Nested(ExpandError),
+}
+
+struct Evaluator<'a> {
+    ctx: &'a mut ShellContext,
+    depth: usize,
+}
+
+impl Evaluator<'_> {
+    fn eval(&mut self, expr: &Expr) -> Result<i64, EvalError> {
+        match expr {
+            Expr::Num(n) => Ok(*n),
+            Expr::Var(name) => self.var(name),
+            Expr::Unary(op, operand) => {
+                let value = self.eval(operand)?;
+                Ok(match *op {
+                    "!" => (value == 0) as i64,
+                    "~" => !value,
+                    "-" => value.wrapping_neg(),
+                    _ => value,
+                })
+            }
+            Expr::Binary("&&", lhs, rhs) => {
+                Ok((self.eval(lhs)? != 0 && self.eval(rhs)? != 0) as i64)
+            }
+            Expr::Binary("||", lhs, rhs) => {
+                Ok((self.eval(lhs)? != 0 || self.eval(rhs)? != 0) as i64)
+            }
+            Expr::Binary(op, lhs, rhs) => {
+                let lhs = self.eval(lhs)?;
+                let rhs = self.eval(rhs)?;
+                apply(op, lhs, rhs).map_err(EvalError::Message)
+            }
+            Expr::Ternary(cond, then, otherwise) => match self.eval(cond)? != 0 {
+                true => self.eval(then),
+                false => self.eval(otherwise),
+            },
+            Expr::Assign(op, name, value) => {
+                let value = self.eval(value)?;
+                let value = match op.strip_suffix('=').filter(|op| !op.is_empty()) {
+                    Some(op) => apply(op, self.var(name)?, value).map_err(EvalError::Message)?,
+                    None => value,
+                };
+                self.set_var(name, value)?;
+                Ok(value)
+            }
+            Expr::PreStep(name, step) => {
+                let value = self.var(name)?.wrapping_add(*step);
+                self.set_var(name, value)?;
+                Ok(value)
+            }
+            Expr::PostStep(name, step) => {
+                let value = self.var(name)?;
+                self.set_var(name, value.wrapping_add(*step))?;
+                Ok(value)
+            }
+        }
+    }
+
+    // Unset or empty variables are 0, anything else is evaluated in turn
+    fn var(&mut self, name: &str) -> Result<i64, EvalError> {
+        let value = self.ctx.env(name).unwrap_or_default().to_string();
+        if let Ok(n) = value.trim().parse::<i64>() {
+            return Ok(n);
+        }
+
+        evaluate_at(&value, self.ctx, self.depth + 1).map_err(EvalError::Nested)
+    }
+
+    fn set_var(&mut self, name: &str, value: i64) -> Result<(), EvalError> {
+        self.ctx
+            .assign(name, &value.to_string())
+            .map_err(EvalError::Message)
+    }
+}
+
+fn apply(op: &str, lhs: i64, rhs: i64) -> Result<i64, String> {
+    Ok(match op {
+        "+" => lhs.wrapping_add(rhs),
+        "-" => lhs.wrapping_sub(rhs),
+        "*" => lhs.wrapping_mul(rhs),
+        "/" | "%" if rhs == 0 => return Err("division by 0".to_string()),
+        "/" => lhs.wrapping_div(rhs),
+        "%" => lhs.wrapping_rem(rhs),
+        "**" if rhs < 0 => return Err("exponent less than 0".to_string()),
+        "**" => lhs.wrapping_pow(rhs.min(u32::MAX as i64) as u32),
+        "<<" => lhs.wrapping_shl(rhs as u32),
+        ">>" => lhs.wrapping_shr(rhs as u32),
+        "<" => (lhs < rhs) as i64,
+        ">" => (lhs > rhs) as i64,
+        "<=" => (lhs <= rhs) as i64,
+        ">=" => (lhs >= rhs) as i64,
+        "==" => (lhs == rhs) as i64,
+        "!=" => (lhs != rhs) as i64,
+        "&" => lhs & rhs,
+        "^" => lhs ^ rhs,
+        "|" => lhs | rhs,
+        "," => rhs,
+        _ => return Err(format!("syntax error: invalid operator `{op}'")),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(text: &str, ctx: &mut ShellContext) -> i64 {
+        evaluate(text, ctx).unwrap()
+    }
+
+    #[test]
+    fn test_precedence() {
+        let mut ctx = ShellContext::new();
+
+        assert_eq!(7, eval("1 + 2 * 3", &mut ctx));
+        assert_eq!(9, eval("(1 + 2) * 3", &mut ctx));
+        assert_eq!(512, eval("2 ** 3 ** 2", &mut ctx));
+        assert_eq!(-1, eval("-7 / 4 + 0", &mut ctx));
+        assert_eq!(1, eval("1 < 2 && 2 <= 2 || 0", &mut ctx));
+        assert_eq!(5, eval("0 ? 3 : 5", &mut ctx));
+        assert_eq!(6, eval("~-7 & 7", &mut ctx));
+        assert_eq!(0, eval("", &mut ctx));
+    }
+
+    #[test]
+    fn test_literals() {
+        let mut ctx = ShellContext::new();
+
+        assert_eq!(255, eval("0xff", &mut ctx));
+        assert_eq!(8, eval("010", &mut ctx));
+        assert_eq!(5, eval("2#101", &mut ctx));
+        assert!(evaluate("09", &mut ctx).is_err());
+    }
+
+    #[test]
+    fn test_variables() {
+        let mut ctx = ShellContext::new();
+        ctx.set_env("A", "4");
+        ctx.set_env("B", "A * 2");
+
+        assert_eq!(8, eval("B", &mut ctx));
+        assert_eq!(6, eval("A += 2", &mut ctx));
+        assert_eq!(6, eval("A++", &mut ctx));
+        assert_eq!(Some("7"), ctx.env("A"));
+        assert_eq!(6, eval("--A", &mut ctx));
+        assert_eq!(0, eval("0 && (A = 100)", &mut ctx));
+        assert_eq!(Some("6"), ctx.env("A"));
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut ctx = ShellContext::new();
+
+        let err = evaluate("1 / 0", &mut ctx).unwrap_err();
+        assert_eq!("1 / 0: division by 0", err.to_string());
+        assert!(evaluate("1 +", &mut ctx).is_err());
+        assert!(evaluate("(1", &mut ctx).is_err());
+        assert!(evaluate("2 3", &mut ctx).is_err());
+    }
+}
@@ -0,0 +1,198 @@
+use super::{Word, WordPart};
+
+// Only unquoted characters take part in brace expansion, everything else is
+// carried through untouched
+#[derive(Debug, Clone)]
+enum Unit {
+    Char(char),
+    Part(WordPart),
+}
+
+/// Expand `{a,b}` alternatives and `{x..y[..step]}` sequences into separate
+/// words, left to right. Words without a valid brace expression are returned
+/// as they are.
+pub fn expand(word: Word) -> Vec<Word> {
+    let mut units = Vec::new();
+    for part in word.parts() {
+        match part {
+            WordPart::Literal(text) => units.extend(text.chars().map(Unit::Char)),
+            part => units.push(Unit::Part(part.clone())),
+        }
+    }
+
+    if !units.iter().any(|u| matches!(u, Unit::Char('{'))) {
+        return vec![word];
+    }
+
+    expand_units(&units)
+        .into_iter()
+        .map(|units| {
+            let mut word = Word::new();
+            for unit in units {
+                match unit {
+                    Unit::Char(ch) => word.push_char(ch, false),
+                    Unit::Part(part) => word.push_part(part),
+                }
+            }
+            word
+        })
+        .filter(|word| !word.is_empty())
+        .collect()
+}
+
+fn expand_units(units: &[Unit]) -> Vec<Vec<Unit>> {
+    for open in 0..units.len() {
+        if !matches!(units[open], Unit::Char('{')) {
+            continue;
+        }
+
+        let Some((close, alternatives)) = parse_brace(units, open) else {
+            continue;
+        };
+
+        let mut result = Vec::new();
+        for alternative in alternatives {
+            let mut joined = units[..open].to_vec();
+            joined.extend(alternative);
+            joined.extend_from_slice(&units[close + 1..]);
+            result.extend(expand_units(&joined));
+        }
+        return result;
+    }
+
+    vec![units.to_vec()]
+}
+
+// Find the brace closing `open` and the alternatives it produces
+fn parse_brace(units: &[Unit], open: usize) -> Option<(usize, Vec<Vec<Unit>>)> {
+    let mut depth = 0;
+    let mut commas = Vec::new();
+
+    for (i, unit) in units.iter().enumerate().skip(open + 1) {
+        match unit {
+            Unit::Char('{') => depth += 1,
+            Unit::Char('}') if depth > 0 => depth -= 1,
+            Unit::Char(',') if depth == 0 => commas.push(i),
+            Unit::Char('}') => {
+                if commas.is_empty() {
+                    let sequence = parse_sequence(&units[open + 1..i])?;
+                    return Some((i, sequence));
+                }
+
+                let mut alternatives = Vec::new();
+                let mut start = open + 1;
+                for &comma in commas.iter().chain(std::iter::once(&i)) {
+                    alternatives.push(units[start..comma].to_vec());
+                    start = comma + 1;
+                }
+                return Some((i, alternatives));
+            }
+            _ => {}
+        }
+    }
+
+    None
+}
+
+// `x..y` or `x..y..step` over integers or single letters
+fn parse_sequence(units: &[Unit]) -> Option<Vec<Vec<Unit>>> {
+    let text = units
+        .iter()
+        .map(|u| match u {
+            Unit::Char(ch) => Some(*ch),
+            Unit::Part(_) => None,
+        })
+        .collect::<Option<String>>()?;
+
+    let fields: Vec<&str> = text.split("..").collect();
+    let (start, end, step) = match fields[..] {
+        [start, end] => (start, end, None),
+        [start, end, step] => (start, end, Some(step.parse::<i64>().ok()?)),
+        _ => return None,
+    };
+    let step = step.unwrap_or(1).unsigned_abs().max(1) as usize;
+
+    let items: Vec<String> = match (start.parse::<i64>(), end.parse::<i64>()) {
+        (Ok(from), Ok(to)) => {
+            // Zero padding applies when either end has a leading zero
+            let padded = |s: &str| {
+                s.trim_start_matches('-').len() > 1 && s.trim_start_matches('-').starts_with('0')
+            };
+            let width = match padded(start) || padded(end) {
+                true => start.len().max(end.len()),
+                false => 0,
+            };
+
+            range(from, to, step)
+                .map(|n| match n < 0 {
+                    true => format!("-{:0>w$}", n.unsigned_abs(), w = width.saturating_sub(1)),
+                    false => format!("{n:0>width$}"),
+                })
+                .collect()
+        }
+        _ => {
+            let (from, to) = (single_letter(start)?, single_letter(end)?);
+            range(from as i64, to as i64, step)
+                .map(|n| char::from(n as u8).to_string())
+                .collect()
+        }
+    };
+
+    Some(
+        items
+            .into_iter()
+            .map(|item| item.chars().map(Unit::Char).collect())
+            .collect(),
+    )
+}
+
+fn single_letter(text: &str) -> Option<char> {
+    let mut chars = text.chars();
+    let ch = chars.next().filter(|c| c.is_ascii_alphabetic())?;
+    chars.next().is_none().then_some(ch)
+}
+
+fn range(from: i64, to: i64, step: usize) -> Box<dyn Iterator<Item = i64>> {
+    match from <= to {
+        true => Box::new((from..=to).step_by(step)),
+        false => Box::new((to..=from).rev().step_by(step)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+
+    fn braces(text: &str) -> Vec<String> {
+        expand(Lexer::lex_word(text))
+            .iter()
+            .map(|w| w.as_plain().unwrap_or_else(|| format!("{w:?}")))
+            .collect()
+    }
+
+    #[test]
+    fn test_alternatives() {
+        assert_eq!(vec!["src/bin", "src/lib"], braces("src/{bin,lib}"));
+        assert_eq!(vec!["ac", "ad", "bc", "bd"], braces("{a,b}{c,d}"));
+        assert_eq!(vec!["a", "b1", "b2", "c"], braces("{a,b{1,2},c}"));
+        assert_eq!(vec!["x", "xy"], braces("x{,y}"));
+        assert_eq!(vec!["{a,b}"], braces("'{a,b}'"));
+        assert_eq!(vec!["{a}", "{}"], [braces("{a}"), braces("{}")].concat());
+    }
+
+    #[test]
+    fn test_sequences() {
+        assert_eq!(vec!["1", "2", "3"], braces("{1..3}"));
+        assert_eq!(vec!["3", "2", "1"], braces("{3..1}"));
+        assert_eq!(vec!["01", "03", "05"], braces("{01..05..2}"));
+        assert_eq!(vec!["-1", "0", "1"], braces("{-1..1}"));
+        assert_eq!(vec!["a", "c", "e"], braces("{a..e..2}"));
+        assert_eq!(vec!["f1", "f2"], braces("f{1..2}"));
+        assert_eq!(vec!["{1..b}"], braces("{1..b}"));
+        assert_eq!(
+            vec!["-9223372036854775808", "-9223372036854775807"],
+            braces("{-9223372036854775808..-9223372036854775807}")
+        );
+    }
+}
@@ -0,0 +1,49 @@
+use std::io::{self, Read, Write};
+use std::os::fd::AsRawFd;
+
+use libc::{WEXITSTATUS, WIFSIGNALED, WTERMSIG, dup2, fork, waitpid};
+
+use super::ExpandError;
+use crate::context::ShellContext;
+use crate::job::List;
+
+/// Run `source` in a subshell and return what it wrote to stdout, minus
+/// trailing newlines. Its exit status is kept as the substitution status.
+pub fn substitute(source: &str, ctx: &mut ShellContext) -> Result<String, ExpandError> {
+    let (mut reader, writer) = io::pipe()?;
+
+    match unsafe { fork() } {
+        -1 => Err(io::Error::last_os_error().into()),
+        0 => {
+            unsafe { dup2(writer.as_raw_fd(), 1) };
+            drop(reader);
+            drop(writer);
+            ctx.enter_subshell();
+
+            let status = List::run_source(source, ctx);
+
+            let _ = io::stdout().flush();
+            std::process::exit(status);
+        }
+        pid => {
+            // Close our write end so the read sees EOF once the subshell exits
+            drop(writer);
+
+            let mut output = Vec::new();
+            let read = reader.read_to_end(&mut output);
+            let mut raw = 0;
+            unsafe { waitpid(pid, &mut raw, 0) };
+            read?;
+
+            let status = match WIFSIGNALED(raw) {
+                true => 128 + WTERMSIG(raw),
+                false => WEXITSTATUS(raw),
+            };
+            ctx.set_substitution_status(status);
+
+            let mut output = String::from_utf8_lossy(&output).into_owned();
+            output.truncate(output.trim_end_matches('\n').len());
+            Ok(output)
+        }
+    }
+}
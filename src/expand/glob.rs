@@ -0,0 +1,112 @@
+use std::fs;
+use std::path::Path;
+
+use super::pattern;
+
+/// Match `pattern` against the filesystem, relative paths are resolved from
+/// `cwd` but returned relative. Results are sorted, and empty if nothing matched.
+pub fn expand(pattern: &str, cwd: &Path, dotglob: bool) -> Vec<String> {
+    // Every prefix is either empty or ends with `/`
+    let (mut prefixes, rest) = match pattern.strip_prefix('/') {
+        Some(rest) => (vec!["/".to_string()], rest),
+        None => (vec![String::new()], pattern),
+    };
+
+    let components: Vec<&str> = rest.split('/').collect();
+    for (i, component) in components.iter().enumerate() {
+        let is_last = i == components.len() - 1;
+        let mut next = Vec::new();
+
+        for prefix in prefixes {
+            let dir = cwd.join(&prefix);
+
+            if component.is_empty() {
+                // `a//b` collapses, a trailing `/` only keeps directories
+                if !is_last || dir.is_dir() {
+                    next.push(prefix);
+                }
+            } else if pattern::has_magic(component) {
+                next.extend(
+                    matching_entries(&dir, component, dotglob)
+                        .into_iter()
+                        .filter(|name| is_last || dir.join(name).is_dir())
+                        .map(|name| join(&prefix, &name, is_last)),
+                );
+            } else {
+                let name = pattern::unescape(component);
+                if !is_last || fs::symlink_metadata(dir.join(&name)).is_ok() {
+                    next.push(join(&prefix, &name, is_last));
+                }
+            }
+        }
+
+        prefixes = next;
+    }
+
+    prefixes.sort();
+    prefixes
+}
+
+fn join(prefix: &str, name: &str, is_last: bool) -> String {
+    match is_last {
+        true => format!("{prefix}{name}"),
+        false => format!("{prefix}{name}/"),
+    }
+}
+
+fn matching_entries(dir: &Path, component: &str, dotglob: bool) -> Vec<String> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+
+    // Hidden files need a literal leading dot unless `dotglob` is set
+    let match_hidden = dotglob || component.starts_with('.') || component.starts_with("\\.");
+
+    entries
+        .flatten()
+        .map(|e| e.file_name().to_string_lossy().into_owned())
+        .filter(|name| match_hidden || !name.starts_with('.'))
+        .filter(|name| pattern::matches(component, name))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand() {
+        let dir = std::env::temp_dir().join(format!("glob-{}", std::process::id()));
+        fs::create_dir_all(dir.join("src/bin")).unwrap();
+        fs::create_dir_all(dir.join("tests")).unwrap();
+        for name in [
+            "src/main.rs",
+            "src/lib.rs",
+            "src/.hidden.rs",
+            "src/bin/tool.rs",
+        ] {
+            fs::File::create(dir.join(name)).unwrap();
+        }
+
+        assert_eq!(
+            vec!["src/lib.rs", "src/main.rs"],
+            expand("src/*.rs", &dir, false)
+        );
+        assert_eq!(
+            vec!["src/.hidden.rs", "src/lib.rs", "src/main.rs"],
+            expand("src/*.rs", &dir, true)
+        );
+        assert_eq!(vec!["src/.hidden.rs"], expand("src/.*.rs", &dir, false));
+        assert_eq!(vec!["src/bin/tool.rs"], expand("*/*/t??l.rs", &dir, false));
+        assert_eq!(vec!["src/", "tests/"], expand("*/", &dir, false));
+        assert!(expand("src/*.txt", &dir, false).is_empty());
+
+        let absolute = format!("{}/s[a-z]c", dir.display());
+        assert_eq!(
+            vec![format!("{}/src", dir.display())],
+            expand(&absolute, &dir, false)
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,561 @@
+use crate::context::{ShellContext, ShellOption};
+use std::{fmt, io};
+
+mod arith;
+mod brace;
+mod command;
+mod glob;
+mod param;
+mod pattern;
+mod tilde;
+
+pub use arith::evaluate as evaluate_arith;
+pub use brace::expand as expand_braces;
+pub use param::ParamExpr;
+
+/// A shell word as produced by the lexer, split into parts so expansion
+/// can be deferred until the word is executed.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Word {
+    parts: Vec<WordPart>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum WordPart {
+    // Unquoted text
+    Literal(String),
+    // Text from quotes or escapes, taken verbatim
+    Quoted(String),
+    // `$NAME` or `${NAME...}`
+    Param { expr: ParamExpr, quoted: bool },
+    // `$(...)` or `` `...` ``, holding the command source
+    Command { source: String, quoted: bool },
+    // `$((...))`, the expression is expanded before it is evaluated
+    Arith { expr: Word, quoted: bool },
+}
+
+impl Word {
+    pub fn new() -> Word {
+        Word { parts: Vec::new() }
+    }
+
+    pub fn literal(text: &str) -> Word {
+        Word {
+            parts: vec![WordPart::Quoted(text.to_string())],
+        }
+    }
+
+    /// A word of plain text as it was typed, without quotes.
+    pub fn unquoted(text: &str) -> Word {
+        Word {
+            parts: vec![WordPart::Literal(text.to_string())],
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.parts.is_empty()
+    }
+
+    pub fn push_char(&mut self, ch: char, quoted: bool) {
+        match (self.parts.last_mut(), quoted) {
+            (Some(WordPart::Literal(s)), false) | (Some(WordPart::Quoted(s)), true) => s.push(ch),
+            (_, false) => self.parts.push(WordPart::Literal(ch.to_string())),
+            (_, true) => self.parts.push(WordPart::Quoted(ch.to_string())),
+        }
+    }
+
+    // Mark the word as present even if the quotes were empty, e.g. `''`
+    pub fn push_empty_quote(&mut self) {
+        if !matches!(self.parts.last(), Some(WordPart::Quoted(_))) {
+            self.parts.push(WordPart::Quoted(String::new()));
+        }
+    }
+
+    pub fn push_part(&mut self, part: WordPart) {
+        self.parts.push(part);
+    }
+
+    pub fn parts(&self) -> &[WordPart] {
+        &self.parts
+    }
+
+    /// Text of a word made only of unquoted literal characters.
+    pub fn as_unquoted(&self) -> Option<&str> {
+        match self.parts.as_slice() {
+            [WordPart::Literal(s)] => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Split an assignment word, `NAME=value`, into the name and value.
+    pub fn split_assignment(&self) -> Option<(String, Word)> {
+        // Only an unquoted `NAME=` counts, `"A=1"` is a command name
+        let (WordPart::Literal(text), rest) = self.parts.split_first()? else {
+            return None;
+        };
+        let (name, value) = text.split_once('=')?;
+        if !is_name(name) {
+            return None;
+        }
+
+        let mut word = Word::new();
+        if !value.is_empty() {
+            word.push_part(WordPart::Literal(value.to_string()));
+        }
+        word.parts.extend(rest.iter().cloned());
+
+        Some((name.to_string(), word))
+    }
+
+    /// Text of a word that needs no expansion at all.
+    pub fn as_plain(&self) -> Option<String> {
+        let mut text = String::new();
+
+        for part in self.parts.iter() {
+            match part {
+                WordPart::Literal(s) if is_glob(s) || s.contains('~') => return None,
+                WordPart::Literal(s) | WordPart::Quoted(s) => text.push_str(s),
+                _ => return None,
+            }
+        }
+
+        Some(text)
+    }
+}
+
+/// The word written back as shell source. Quoting is normalised, so the text
+/// may differ from what was typed but reads back as the same word.
+impl fmt::Display for Word {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, part) in self.parts.iter().enumerate() {
+            match part {
+                WordPart::Literal(text) => write!(f, "{text}")?,
+                // Left by `""` in front of an expansion
+                WordPart::Quoted(text) if text.is_empty() && self.parts.len() > 1 => {}
+                WordPart::Quoted(text) => write_quoted(f, text)?,
+                WordPart::Param { expr, quoted: true } => write!(f, "\"{expr}\"")?,
+                WordPart::Param { expr, .. } => {
+                    // Keep a following name character from joining the name
+                    let joins = match self.parts.get(i + 1) {
+                        Some(WordPart::Literal(next)) => next.starts_with(|c: char| {
+                            c == '_' || c == '[' || c.is_ascii_alphanumeric()
+                        }),
+                        _ => false,
+                    };
+                    match joins {
+                        true => write!(f, "{expr:#}")?,
+                        false => write!(f, "{expr}")?,
+                    }
+                }
+                WordPart::Command { source, quoted } => match quoted {
+                    true => write!(f, "\"$({source})\"")?,
+                    false => write!(f, "$({source})")?,
+                },
+                WordPart::Arith { expr, quoted } => {
+                    let mut text = String::new();
+                    for part in expr.parts() {
+                        match part {
+                            WordPart::Literal(s) | WordPart::Quoted(s) => text.push_str(s),
+                            part => text.push_str(&Word::from(part.clone()).to_string()),
+                        }
+                    }
+                    match quoted {
+                        true => write!(f, "\"$(({text}))\"")?,
+                        false => write!(f, "$(({text}))")?,
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl From<WordPart> for Word {
+    fn from(part: WordPart) -> Word {
+        Word { parts: vec![part] }
+    }
+}
+
+// Quoted text in single quotes, unless it doesn't need any
+fn write_quoted(f: &mut fmt::Formatter<'_>, text: &str) -> fmt::Result {
+    let plain = !text.is_empty()
+        && text
+            .chars()
+            .all(|c| c.is_alphanumeric() || "-_./,:+=@%^".contains(c));
+
+    match plain {
+        true => write!(f, "{text}"),
+        false => write!(f, "'{}'", text.replace('\'', r"'\''")),
+    }
+}
+
+#[derive(Debug)]
+pub enum ExpandError {
+    // `${NAME:?message}` and friends
+    Param { name: String, message: String },
+    BadSubstitution(String),
+    Arith { expr: String, message: String },
+    // A glob matched nothing with `failglob` set
+    NoMatch(String),
+    Io(io::Error),
+}
+
+impl fmt::Display for ExpandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExpandError::Param { name, message } => write!(f, "{name}: {message}"),
+            ExpandError::BadSubstitution(text) => write!(f, "{text}: bad substitution"),
+            ExpandError::Arith { expr, message } => write!(f, "{expr}: {message}"),
+            ExpandError::NoMatch(pattern) => write!(f, "no match: {pattern}"),
+            ExpandError::Io(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for ExpandError {}
+
+impl From<io::Error> for ExpandError {
+    fn from(e: io::Error) -> Self {
+        ExpandError::Io(e)
+    }
+}
+
+/// Expand a word into fields, splitting unquoted expansion results on `IFS`
+/// and matching unquoted glob patterns against the filesystem.
+pub fn expand_word(word: &Word, ctx: &mut ShellContext) -> Result<Vec<String>, ExpandError> {
+    let mut fields = Fields::new(ctx.env("IFS").unwrap_or(DEFAULT_IFS));
+
+    for part in tilde::expand(word.parts(), ctx).iter() {
+        match part {
+            WordPart::Literal(s) => fields.push_literal(s),
+            WordPart::Quoted(s) => fields.push_quoted(s),
+            WordPart::Param { expr, quoted: true } if let Some(values) = expr.elements(ctx) => {
+                fields.push_elements(&values);
+            }
+            part => {
+                let (value, quoted) = expand_part(part, ctx)?;
+                if quoted {
+                    fields.push_quoted(&value);
+                } else {
+                    fields.push_split(&value);
+                }
+            }
+        }
+    }
+
+    let mut result = Vec::new();
+    for field in fields.finish() {
+        if !field.glob || !pattern::has_magic(&field.pattern) {
+            result.push(field.text);
+            continue;
+        }
+
+        let matches = glob::expand(&field.pattern, ctx.cwd(), ctx.option(ShellOption::Dotglob));
+        if !matches.is_empty() {
+            result.extend(matches);
+        } else if ctx.option(ShellOption::Failglob) {
+            return Err(ExpandError::NoMatch(field.text));
+        } else if !ctx.option(ShellOption::Nullglob) {
+            // POSIX leaves an unmatched pattern as is
+            result.push(field.text);
+        }
+    }
+
+    Ok(result)
+}
+
+/// Expand a word into a single string without field splitting, as used for
+/// redirect targets.
+pub fn expand_string(word: &Word, ctx: &mut ShellContext) -> Result<String, ExpandError> {
+    let mut text = String::new();
+
+    for part in tilde::expand(word.parts(), ctx).iter() {
+        match part {
+            WordPart::Literal(s) | WordPart::Quoted(s) => text.push_str(s),
+            part => text.push_str(&expand_part(part, ctx)?.0),
+        }
+    }
+
+    Ok(text)
+}
+
+/// Expand a word into a pattern where only unquoted characters stay special.
+pub fn expand_pattern(word: &Word, ctx: &mut ShellContext) -> Result<String, ExpandError> {
+    let mut text = String::new();
+
+    for part in word.parts() {
+        match part {
+            WordPart::Literal(s) => text.push_str(s),
+            WordPart::Quoted(s) => text.push_str(&pattern::escape(s)),
+            part => {
+                let (value, quoted) = expand_part(part, ctx)?;
+                match quoted {
+                    true => text.push_str(&pattern::escape(&value)),
+                    false => text.push_str(&value),
+                }
+            }
+        }
+    }
+
+    Ok(text)
+}
+
+// Value of an expansion part along with whether it was quoted
+fn expand_part(part: &WordPart, ctx: &mut ShellContext) -> Result<(String, bool), ExpandError> {
+    match part {
+        WordPart::Literal(s) => Ok((s.clone(), false)),
+        WordPart::Quoted(s) => Ok((s.clone(), true)),
+        WordPart::Param { expr, quoted } => Ok((expr.expand(ctx)?, *quoted)),
+        WordPart::Command { source, quoted } => Ok((command::substitute(source, ctx)?, *quoted)),
+        WordPart::Arith { expr, quoted } => {
+            let text = expand_string(expr, ctx)?;
+            Ok((arith::evaluate(&text, ctx)?.to_string(), *quoted))
+        }
+    }
+}
+
+fn is_glob(text: &str) -> bool {
+    text.contains(['*', '?', '['])
+}
+
+/// Whether `text` is a valid variable name.
+pub fn is_name(text: &str) -> bool {
+    let mut chars = text.chars();
+    matches!(chars.next(), Some(c) if c == '_' || c.is_ascii_alphabetic())
+        && chars.all(|c| c == '_' || c.is_ascii_alphanumeric())
+}
+
+pub fn is_special_param(ch: char) -> bool {
+    matches!(ch, '?' | '$' | '!' | '#' | '@' | '*' | '-') || ch.is_ascii_digit()
+}
+
+pub(super) fn lookup_param(name: &str, ctx: &ShellContext) -> Option<String> {
+    // `NAME[i]`, `NAME[@]` and `NAME[*]`, elements are joined with spaces
+    if let Some((base, index)) = split_subscript(name) {
+        let values = array_values(base, ctx);
+        return match index {
+            "@" | "*" => Some(values.join(" ")),
+            _ => index
+                .parse::<usize>()
+                .ok()
+                .and_then(|i| values.get(i).cloned()),
+        };
+    }
+
+    match name {
+        "PIPESTATUS" => array_values(name, ctx).into_iter().next(),
+        "$" => Some(ctx.pid().to_string()),
+        "0" => std::env::args().next(),
+        "#" => Some("0".to_string()),
+        "?" => Some(ctx.status().to_string()),
+        "!" => ctx.jobs().last_pid().map(|pid| pid.to_string()),
+        _ => ctx.env(name).map(str::to_string),
+    }
+}
+
+pub(super) fn split_subscript(name: &str) -> Option<(&str, &str)> {
+    name.strip_suffix(']')?.split_once('[')
+}
+
+// Only `PIPESTATUS` is an array, any other variable acts as one element
+pub(super) fn array_values(name: &str, ctx: &ShellContext) -> Vec<String> {
+    match name {
+        "PIPESTATUS" => ctx.pipestatus().iter().map(i32::to_string).collect(),
+        _ => lookup_param(name, ctx).into_iter().collect(),
+    }
+}
+
+const DEFAULT_IFS: &str = " \t\n";
+
+#[derive(Default)]
+struct Field {
+    text: String,
+    // The same text with quoted characters escaped, for globbing
+    pattern: String,
+    glob: bool,
+}
+
+struct Fields {
+    ifs: String,
+    fields: Vec<Field>,
+    current: Field,
+    // Whether `current` should be kept even when empty
+    started: bool,
+}
+
+impl Fields {
+    fn new(ifs: &str) -> Fields {
+        Fields {
+            ifs: ifs.to_string(),
+            fields: Vec::new(),
+            current: Field::default(),
+            started: false,
+        }
+    }
+
+    fn push_literal(&mut self, s: &str) {
+        self.current.text.push_str(s);
+        self.current.pattern.push_str(s);
+        self.current.glob |= is_glob(s);
+        self.started = true;
+    }
+
+    fn push_quoted(&mut self, s: &str) {
+        self.current.text.push_str(s);
+        self.current.pattern.push_str(&pattern::escape(s));
+        self.started = true;
+    }
+
+    fn push_split(&mut self, s: &str) {
+        for ch in s.chars() {
+            if self.ifs.contains(ch) {
+                let is_whitespace = ch.is_ascii_whitespace();
+                // Runs of IFS whitespace only delimit, other IFS chars always do
+                if self.started || !is_whitespace {
+                    self.end_field();
+                }
+            } else {
+                self.push_literal(ch.encode_utf8(&mut [0; 4]));
+            }
+        }
+    }
+
+    // Quoted `${NAME[@]}`, the elements are kept apart in fields of their own
+    fn push_elements(&mut self, values: &[String]) {
+        for (i, value) in values.iter().enumerate() {
+            if i > 0 {
+                self.end_field();
+            }
+            self.push_quoted(value);
+        }
+    }
+
+    fn end_field(&mut self) {
+        self.fields.push(std::mem::take(&mut self.current));
+        self.started = false;
+    }
+
+    fn finish(mut self) -> Vec<Field> {
+        if self.started {
+            self.end_field();
+        }
+
+        self.fields
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn param(name: &str, quoted: bool) -> WordPart {
+        WordPart::Param {
+            expr: ParamExpr::plain(name),
+            quoted,
+        }
+    }
+
+    #[test]
+    fn test_expand_param() {
+        let mut ctx = ShellContext::new();
+        ctx.set_env("FOO", "a  b");
+
+        let mut word = Word::new();
+        word.push_char('x', false);
+        word.push_part(param("FOO", false));
+        assert_eq!(vec!["xa", "b"], expand_word(&word, &mut ctx).unwrap());
+
+        let mut word = Word::new();
+        word.push_part(param("FOO", true));
+        assert_eq!(vec!["a  b"], expand_word(&word, &mut ctx).unwrap());
+    }
+
+    #[test]
+    fn test_shell_pid() {
+        let mut ctx = ShellContext::new();
+        let run = |source, ctx: &mut ShellContext| crate::job::List::run_source(source, ctx);
+
+        // Subshells see the same pid as the shell
+        run("a=$$", &mut ctx);
+        assert_eq!(1, run("b=$(exit $(($$ == a)))", &mut ctx));
+        assert_eq!(1, run("(exit $(($$ == a)))", &mut ctx));
+    }
+
+    #[test]
+    fn test_expand_unset() {
+        let mut ctx = ShellContext::new();
+        ctx.unset_env("UNSET_VAR");
+
+        let mut word = Word::new();
+        word.push_part(param("UNSET_VAR", false));
+        assert!(expand_word(&word, &mut ctx).unwrap().is_empty());
+
+        let mut word = Word::new();
+        word.push_part(param("UNSET_VAR", true));
+        assert_eq!(vec![""], expand_word(&word, &mut ctx).unwrap());
+    }
+
+    #[test]
+    fn test_pipestatus() {
+        let mut ctx = ShellContext::new();
+        ctx.set_pipestatus(vec![1, 0, 141]);
+        ctx.set_env("FOO", "x");
+
+        assert_eq!(Some("1".to_string()), lookup_param("PIPESTATUS", &ctx));
+        assert_eq!(Some("141".to_string()), lookup_param("PIPESTATUS[2]", &ctx));
+        assert_eq!(None, lookup_param("PIPESTATUS[3]", &ctx));
+        assert_eq!(
+            Some("1 0 141".to_string()),
+            lookup_param("PIPESTATUS[@]", &ctx)
+        );
+        assert_eq!(Some("x".to_string()), lookup_param("FOO[0]", &ctx));
+        assert_eq!(None, lookup_param("FOO[1]", &ctx));
+
+        // Quoted, `[@]` keeps the elements apart and `[*]` joins them
+        let mut word = Word::new();
+        word.push_char('x', true);
+        word.push_part(param("PIPESTATUS[@]", true));
+        assert_eq!(
+            vec!["x1", "0", "141"],
+            expand_word(&word, &mut ctx).unwrap()
+        );
+
+        let mut word = Word::new();
+        word.push_part(param("PIPESTATUS[*]", true));
+        assert_eq!(vec!["1 0 141"], expand_word(&word, &mut ctx).unwrap());
+    }
+
+    #[test]
+    fn test_expand_glob() {
+        let dir = std::env::temp_dir().join(format!("expand-glob-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for name in ["b.rs", "a.rs", ".hidden.rs", "c.txt"] {
+            std::fs::File::create(dir.join(name)).unwrap();
+        }
+
+        let mut ctx = ShellContext::new();
+        ctx.set_cwd(&dir);
+
+        let mut word = Word::new();
+        word.push_char('*', false);
+        word.push_part(WordPart::Quoted(".rs".to_string()));
+        assert_eq!(vec!["a.rs", "b.rs"], expand_word(&word, &mut ctx).unwrap());
+
+        let mut word = Word::new();
+        word.push_part(WordPart::Quoted("*".to_string()));
+        word.push_part(WordPart::Literal(".rs".to_string()));
+        assert_eq!(vec!["*.rs"], expand_word(&word, &mut ctx).unwrap());
+
+        let mut word = Word::new();
+        word.push_part(WordPart::Literal("*.none".to_string()));
+        assert_eq!(vec!["*.none"], expand_word(&word, &mut ctx).unwrap());
+
+        ctx.set_option(ShellOption::Nullglob, true);
+        assert!(expand_word(&word, &mut ctx).unwrap().is_empty());
+
+        ctx.set_option(ShellOption::Failglob, true);
+        assert!(expand_word(&word, &mut ctx).is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,508 @@
+use super::{
+    ExpandError, Word, array_values, expand_pattern, expand_string, is_name, is_special_param,
+    lookup_param, split_subscript,
+};
+use crate::context::ShellContext;
+use crate::lexer::Lexer;
+use std::fmt;
+
+use super::pattern;
+
+/// A parameter expansion, `$NAME` or one of the `${...}` forms.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParamExpr {
+    name: String,
+    op: ParamOp,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum ParamOp {
+    None,
+    // ${#NAME}
+    Length,
+    // ${NAME:-word} / ${NAME-word}
+    Default {
+        word: Word,
+        colon: bool,
+    },
+    // ${NAME:=word} / ${NAME=word}
+    Assign {
+        word: Word,
+        colon: bool,
+    },
+    // ${NAME:?word} / ${NAME?word}
+    Error {
+        word: Word,
+        colon: bool,
+    },
+    // ${NAME:+word} / ${NAME+word}
+    Alternative {
+        word: Word,
+        colon: bool,
+    },
+    // ${NAME#pat} / ${NAME##pat}
+    RemovePrefix {
+        pattern: Word,
+        longest: bool,
+    },
+    // ${NAME%pat} / ${NAME%%pat}
+    RemoveSuffix {
+        pattern: Word,
+        longest: bool,
+    },
+    // ${NAME/pat/rep} and the `//`, `/#`, `/%` variants
+    Replace {
+        pattern: Word,
+        replacement: Word,
+        mode: ReplaceMode,
+    },
+    // Anything we couldn't parse, reported when expanded
+    Bad(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ReplaceMode {
+    First,
+    All,
+    Prefix,
+    Suffix,
+}
+
+impl ParamExpr {
+    pub fn plain(name: &str) -> ParamExpr {
+        ParamExpr {
+            name: name.to_string(),
+            op: ParamOp::None,
+        }
+    }
+
+    /// Parse the body of `${...}`.
+    pub fn parse(body: &str) -> ParamExpr {
+        Self::try_parse(body).unwrap_or_else(|| ParamExpr {
+            name: String::new(),
+            op: ParamOp::Bad(body.to_string()),
+        })
+    }
+
+    fn try_parse(body: &str) -> Option<ParamExpr> {
+        // `${#NAME}` but `${#}` alone is the special parameter
+        if let Some(rest) = body.strip_prefix('#')
+            && !rest.is_empty()
+        {
+            let (name, rest) = split_name(rest)?;
+            return rest.is_empty().then(|| ParamExpr {
+                name: name.to_string(),
+                op: ParamOp::Length,
+            });
+        }
+
+        let (name, rest) = split_name(body)?;
+        let (colon, rest) = match rest.strip_prefix(':') {
+            Some(rest) => (true, rest),
+            None => (false, rest),
+        };
+
+        let mut chars = rest.chars();
+        let op = match (chars.next(), colon) {
+            (None, false) => ParamOp::None,
+            (Some('-'), _) => ParamOp::Default {
+                word: Lexer::lex_word(chars.as_str()),
+                colon,
+            },
+            (Some('='), _) => ParamOp::Assign {
+                word: Lexer::lex_word(chars.as_str()),
+                colon,
+            },
+            (Some('?'), _) => ParamOp::Error {
+                word: Lexer::lex_word(chars.as_str()),
+                colon,
+            },
+            (Some('+'), _) => ParamOp::Alternative {
+                word: Lexer::lex_word(chars.as_str()),
+                colon,
+            },
+            (Some('#'), false) => match chars.as_str().strip_prefix('#') {
+                Some(pat) => ParamOp::RemovePrefix {
+                    pattern: Lexer::lex_word(pat),
+                    longest: true,
+                },
+                None => ParamOp::RemovePrefix {
+                    pattern: Lexer::lex_word(chars.as_str()),
+                    longest: false,
+                },
+            },
+            (Some('%'), false) => match chars.as_str().strip_prefix('%') {
+                Some(pat) => ParamOp::RemoveSuffix {
+                    pattern: Lexer::lex_word(pat),
+                    longest: true,
+                },
+                None => ParamOp::RemoveSuffix {
+                    pattern: Lexer::lex_word(chars.as_str()),
+                    longest: false,
+                },
+            },
+            (Some('/'), false) => {
+                let rest = chars.as_str();
+                let (mode, rest) = match rest.chars().next() {
+                    Some('/') => (ReplaceMode::All, &rest[1..]),
+                    Some('#') => (ReplaceMode::Prefix, &rest[1..]),
+                    Some('%') => (ReplaceMode::Suffix, &rest[1..]),
+                    _ => (ReplaceMode::First, rest),
+                };
+                let (pat, rep) = split_replace(rest);
+                ParamOp::Replace {
+                    pattern: Lexer::lex_word(pat),
+                    replacement: Lexer::lex_word(rep),
+                    mode,
+                }
+            }
+            _ => return None,
+        };
+
+        Some(ParamExpr {
+            name: name.to_string(),
+            op,
+        })
+    }
+
+    /// The elements of `${NAME[@]}`, which expands to a field for each one
+    /// when quoted rather than to a single string.
+    pub fn elements(&self, ctx: &ShellContext) -> Option<Vec<String>> {
+        match (&self.op, split_subscript(&self.name)) {
+            (ParamOp::None, Some((base, "@"))) => Some(array_values(base, ctx)),
+            _ => None,
+        }
+    }
+
+    pub fn expand(&self, ctx: &mut ShellContext) -> Result<String, ExpandError> {
+        let value = lookup_param(&self.name, ctx);
+        // With a colon, an empty value counts as unset
+        let is_set = |colon: bool| value.as_deref().is_some_and(|v| !colon || !v.is_empty());
+
+        let result = match &self.op {
+            ParamOp::None => value.unwrap_or_default(),
+            ParamOp::Length => match split_subscript(&self.name) {
+                // `${#NAME[@]}` counts elements
+                Some((base, "@" | "*")) => array_values(base, ctx).len().to_string(),
+                _ => value.unwrap_or_default().chars().count().to_string(),
+            },
+            ParamOp::Default { word, colon } => match is_set(*colon) {
+                true => value.unwrap_or_default(),
+                false => expand_string(word, ctx)?,
+            },
+            ParamOp::Assign { word, colon } => match is_set(*colon) {
+                true => value.unwrap_or_default(),
+                false => {
+                    if !is_assignable(&self.name) {
+                        return Err(ExpandError::Param {
+                            name: self.name.clone(),
+                            message: "cannot assign in this way".to_string(),
+                        });
+                    }
+
+                    let assigned = expand_string(word, ctx)?;
+                    if ctx.is_readonly(&self.name) {
+                        return Err(ExpandError::Param {
+                            name: self.name.clone(),
+                            message: "readonly variable".to_string(),
+                        });
+                    }
+                    ctx.set_env(&self.name, &assigned);
+                    assigned
+                }
+            },
+            ParamOp::Error { word, colon } => match is_set(*colon) {
+                true => value.unwrap_or_default(),
+                false => {
+                    let mut message = expand_string(word, ctx)?;
+                    if message.is_empty() {
+                        message = match colon {
+                            true => "parameter null or not set".to_string(),
+                            false => "parameter not set".to_string(),
+                        };
+                    }
+
+                    return Err(ExpandError::Param {
+                        name: self.name.clone(),
+                        message,
+                    });
+                }
+            },
+            ParamOp::Alternative { word, colon } => match is_set(*colon) {
+                true => expand_string(word, ctx)?,
+                false => String::new(),
+            },
+            ParamOp::RemovePrefix { pattern, longest } => {
+                let value = value.unwrap_or_default();
+                let pattern = expand_pattern(pattern, ctx)?;
+                remove_prefix(&value, &pattern, *longest).to_string()
+            }
+            ParamOp::RemoveSuffix { pattern, longest } => {
+                let value = value.unwrap_or_default();
+                let pattern = expand_pattern(pattern, ctx)?;
+                remove_suffix(&value, &pattern, *longest).to_string()
+            }
+            ParamOp::Replace {
+                pattern,
+                replacement,
+                mode,
+            } => {
+                let value = value.unwrap_or_default();
+                let pattern = expand_pattern(pattern, ctx)?;
+                let replacement = expand_string(replacement, ctx)?;
+                replace(&value, &pattern, &replacement, *mode)
+            }
+            ParamOp::Bad(body) => return Err(ExpandError::BadSubstitution(format!("${{{body}}}"))),
+        };
+
+        Ok(result)
+    }
+}
+
+// `$NAME` where that's unambiguous, `${...}` otherwise. The alternate flag
+// asks for braces even around a plain name, for when a name character follows.
+impl fmt::Display for ParamExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = &self.name;
+        let colon = |colon: bool| if colon { ":" } else { "" };
+
+        match &self.op {
+            ParamOp::None if !f.alternate() && (is_name(name) || name.len() == 1) => {
+                write!(f, "${name}")
+            }
+            ParamOp::None => write!(f, "${{{name}}}"),
+            ParamOp::Length => write!(f, "${{#{name}}}"),
+            ParamOp::Default { word, colon: c } => write!(f, "${{{name}{}-{word}}}", colon(*c)),
+            ParamOp::Assign { word, colon: c } => write!(f, "${{{name}{}={word}}}", colon(*c)),
+            ParamOp::Error { word, colon: c } => write!(f, "${{{name}{}?{word}}}", colon(*c)),
+            ParamOp::Alternative { word, colon: c } => {
+                write!(f, "${{{name}{}+{word}}}", colon(*c))
+            }
+            ParamOp::RemovePrefix { pattern, longest } => {
+                let op = if *longest { "##" } else { "#" };
+                write!(f, "${{{name}{op}{pattern}}}")
+            }
+            ParamOp::RemoveSuffix { pattern, longest } => {
+                let op = if *longest { "%%" } else { "%" };
+                write!(f, "${{{name}{op}{pattern}}}")
+            }
+            ParamOp::Replace {
+                pattern,
+                replacement,
+                mode,
+            } => {
+                let op = match mode {
+                    ReplaceMode::First => "/",
+                    ReplaceMode::All => "//",
+                    ReplaceMode::Prefix => "/#",
+                    ReplaceMode::Suffix => "/%",
+                };
+                write!(f, "${{{name}{op}{pattern}/{replacement}}}")
+            }
+            ParamOp::Bad(body) => write!(f, "${{{body}}}"),
+        }
+    }
+}
+
+// Split a parameter name off the front of `text`
+fn split_name(text: &str) -> Option<(&str, &str)> {
+    let first = text.chars().next()?;
+
+    let len = if first == '_' || first.is_ascii_alphabetic() {
+        let len = text
+            .find(|c: char| c != '_' && !c.is_ascii_alphanumeric())
+            .unwrap_or(text.len());
+
+        // Include a subscript, `NAME[i]`
+        match text[len..]
+            .strip_prefix('[')
+            .and_then(|rest| rest.find(']'))
+        {
+            Some(end) => len + end + 2,
+            None => len,
+        }
+    } else if first.is_ascii_digit() {
+        text.find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(text.len())
+    } else if is_special_param(first) {
+        first.len_utf8()
+    } else {
+        return None;
+    };
+
+    Some(text.split_at(len))
+}
+
+// Split `pat/rep` on the first `/` that isn't escaped or quoted
+fn split_replace(text: &str) -> (&str, &str) {
+    let mut chars = text.char_indices();
+    let mut quote = None;
+
+    while let Some((i, ch)) = chars.next() {
+        match (ch, quote) {
+            ('\\', _) => {
+                chars.next();
+            }
+            ('\'' | '"', None) => quote = Some(ch),
+            (c, Some(q)) if c == q => quote = None,
+            ('/', None) => return (&text[..i], &text[i + 1..]),
+            _ => {}
+        }
+    }
+
+    (text, "")
+}
+
+fn is_assignable(name: &str) -> bool {
+    !name.starts_with(|c: char| c.is_ascii_digit() || is_special_param(c))
+}
+
+// Byte offsets of every char boundary in `text`, including the end
+fn boundaries(text: &str) -> Vec<usize> {
+    text.char_indices()
+        .map(|(i, _)| i)
+        .chain(std::iter::once(text.len()))
+        .collect()
+}
+
+fn remove_prefix<'a>(value: &'a str, pattern: &str, longest: bool) -> &'a str {
+    let mut ends = boundaries(value);
+    if longest {
+        ends.reverse();
+    }
+
+    ends.into_iter()
+        .find(|&end| pattern::matches(pattern, &value[..end]))
+        .map(|end| &value[end..])
+        .unwrap_or(value)
+}
+
+fn remove_suffix<'a>(value: &'a str, pattern: &str, longest: bool) -> &'a str {
+    let mut starts = boundaries(value);
+    if !longest {
+        starts.reverse();
+    }
+
+    starts
+        .into_iter()
+        .find(|&start| pattern::matches(pattern, &value[start..]))
+        .map(|start| &value[..start])
+        .unwrap_or(value)
+}
+
+fn replace(value: &str, pattern: &str, replacement: &str, mode: ReplaceMode) -> String {
+    let bounds = boundaries(value);
+
+    // Longest match starting at `start`, must be non-empty except when anchored
+    let longest_match = |start: usize| {
+        bounds
+            .iter()
+            .rev()
+            .copied()
+            .filter(|&end| end > start)
+            .find(|&end| pattern::matches(pattern, &value[start..end]))
+    };
+
+    match mode {
+        ReplaceMode::Prefix => match longest_match(0) {
+            Some(end) => format!("{replacement}{}", &value[end..]),
+            None => value.to_string(),
+        },
+        ReplaceMode::Suffix => bounds
+            .iter()
+            .copied()
+            .filter(|&start| start < value.len())
+            .find(|&start| pattern::matches(pattern, &value[start..]))
+            .map(|start| format!("{}{replacement}", &value[..start]))
+            .unwrap_or_else(|| value.to_string()),
+        ReplaceMode::First | ReplaceMode::All => {
+            let mut result = String::new();
+            let mut pos = 0;
+            let mut replaced = false;
+
+            for &start in bounds.iter() {
+                if start < pos || start == value.len() {
+                    continue;
+                }
+                if replaced && mode == ReplaceMode::First {
+                    break;
+                }
+
+                if let Some(end) = longest_match(start) {
+                    result.push_str(&value[pos..start]);
+                    result.push_str(replacement);
+                    pos = end;
+                    replaced = true;
+                }
+            }
+
+            result.push_str(&value[pos..]);
+            result
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand(body: &str, ctx: &mut ShellContext) -> Result<String, ExpandError> {
+        ParamExpr::parse(body).expand(ctx)
+    }
+
+    #[test]
+    fn test_default_and_alternative() {
+        let mut ctx = ShellContext::new();
+        ctx.set_env("EMPTY", "");
+        ctx.set_env("FULL", "value");
+
+        assert_eq!("value", expand("FULL:-other", &mut ctx).unwrap());
+        assert_eq!("other", expand("EMPTY:-other", &mut ctx).unwrap());
+        assert_eq!("", expand("EMPTY-other", &mut ctx).unwrap());
+        assert_eq!("alt", expand("FULL:+alt", &mut ctx).unwrap());
+        assert_eq!("", expand("EMPTY:+alt", &mut ctx).unwrap());
+        assert_eq!("alt", expand("EMPTY+alt", &mut ctx).unwrap());
+    }
+
+    #[test]
+    fn test_assign_and_error() {
+        let mut ctx = ShellContext::new();
+        ctx.set_env("EMPTY", "");
+
+        assert_eq!("set", expand("EMPTY:=set", &mut ctx).unwrap());
+        assert_eq!(Some("set"), ctx.env("EMPTY"));
+
+        let err = expand("NOT_SET_AT_ALL:?custom message", &mut ctx).unwrap_err();
+        assert_eq!("NOT_SET_AT_ALL: custom message", err.to_string());
+        assert!(expand("1:=x", &mut ctx).is_err());
+        assert!(expand("FULL:", &mut ctx).is_err());
+    }
+
+    #[test]
+    fn test_length_and_trim() {
+        let mut ctx = ShellContext::new();
+        ctx.set_env("FILE", "dir/archive.tar.gz");
+
+        assert_eq!("18", expand("#FILE", &mut ctx).unwrap());
+        assert_eq!("archive.tar.gz", expand("FILE#*/", &mut ctx).unwrap());
+        assert_eq!("gz", expand("FILE##*.", &mut ctx).unwrap());
+        assert_eq!("dir/archive.tar", expand("FILE%.*", &mut ctx).unwrap());
+        assert_eq!("dir/archive", expand("FILE%%.*", &mut ctx).unwrap());
+        assert_eq!(
+            "dir/archive.tar.gz",
+            expand("FILE#\"*\"", &mut ctx).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_replace() {
+        let mut ctx = ShellContext::new();
+        ctx.set_env("PATHS", "a:b:c");
+
+        assert_eq!("a b:c", expand("PATHS/:/ ", &mut ctx).unwrap());
+        assert_eq!("a b c", expand("PATHS//:/ ", &mut ctx).unwrap());
+        assert_eq!("x:b:c", expand("PATHS/#a/x", &mut ctx).unwrap());
+        assert_eq!("a:b:x", expand("PATHS/%c/x", &mut ctx).unwrap());
+        assert_eq!("abc", expand("PATHS//:", &mut ctx).unwrap());
+    }
+}
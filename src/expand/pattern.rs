@@ -0,0 +1,241 @@
+// Shell pattern matching for `*`, `?` and `[...]`, a backslash makes the
+// next character literal.
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Char(char),
+    Any,
+    Star,
+    Class {
+        negated: bool,
+        items: Vec<ClassItem>,
+    },
+}
+
+#[derive(Debug, PartialEq)]
+enum ClassItem {
+    Char(char),
+    Range(char, char),
+    Named(String),
+}
+
+pub fn matches(pattern: &str, text: &str) -> bool {
+    let tokens = compile(pattern);
+    let text: Vec<char> = text.chars().collect();
+
+    match_tokens(&tokens, &text)
+}
+
+/// Whether the pattern contains any unescaped special character.
+pub fn has_magic(pattern: &str) -> bool {
+    compile(pattern)
+        .iter()
+        .any(|t| !matches!(t, Token::Char(_)))
+}
+
+/// Remove escapes, giving the literal text a pattern stands for.
+pub fn unescape(pattern: &str) -> String {
+    let mut text = String::with_capacity(pattern.len());
+    let mut chars = pattern.chars();
+
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' => text.extend(chars.next()),
+            _ => text.push(ch),
+        }
+    }
+
+    text
+}
+
+/// Escape every pattern character so `text` only matches itself.
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for ch in text.chars() {
+        if matches!(ch, '*' | '?' | '[' | ']' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(ch);
+    }
+
+    escaped
+}
+
+fn compile(pattern: &str) -> Vec<Token> {
+    let chars: Vec<char> = pattern.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        match chars[i] {
+            '\\' if i + 1 < chars.len() => {
+                tokens.push(Token::Char(chars[i + 1]));
+                i += 2;
+                continue;
+            }
+            '*' => {
+                // Collapse runs of stars
+                if tokens.last() != Some(&Token::Star) {
+                    tokens.push(Token::Star);
+                }
+            }
+            '?' => tokens.push(Token::Any),
+            '[' => match compile_class(&chars, i + 1) {
+                Some((class, end)) => {
+                    tokens.push(class);
+                    i = end;
+                }
+                // An unterminated bracket is just a character
+                None => tokens.push(Token::Char('[')),
+            },
+            ch => tokens.push(Token::Char(ch)),
+        }
+        i += 1;
+    }
+
+    tokens
+}
+
+// Parse a bracket expression starting after `[`, returning the class and the
+// index of the closing `]`.
+fn compile_class(chars: &[char], start: usize) -> Option<(Token, usize)> {
+    let mut i = start;
+    let mut negated = false;
+    let mut items = Vec::new();
+
+    if matches!(chars.get(i), Some('!' | '^')) {
+        negated = true;
+        i += 1;
+    }
+
+    // A leading `]` is taken literally
+    let first = i;
+    loop {
+        let ch = *chars.get(i)?;
+
+        match ch {
+            ']' if i > first => return Some((Token::Class { negated, items }, i)),
+            '[' if chars.get(i + 1) == Some(&':') => {
+                let rest: String = chars[i + 2..].iter().collect();
+                let end = rest.find(":]")?;
+                items.push(ClassItem::Named(rest[..end].to_string()));
+                i += 2 + rest[..end].chars().count() + 2;
+                continue;
+            }
+            '\\' => {
+                i += 1;
+                items.push(ClassItem::Char(*chars.get(i)?));
+            }
+            _ => {
+                // `a-z` range, a trailing `-` is literal
+                if chars.get(i + 1) == Some(&'-') && chars.get(i + 2).is_some_and(|c| *c != ']') {
+                    items.push(ClassItem::Range(ch, chars[i + 2]));
+                    i += 3;
+                    continue;
+                }
+                items.push(ClassItem::Char(ch));
+            }
+        }
+        i += 1;
+    }
+}
+
+fn match_tokens(tokens: &[Token], text: &[char]) -> bool {
+    // Iterative matching with backtracking to the last star
+    let (mut t, mut p) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        match tokens.get(p) {
+            Some(Token::Star) => {
+                star = Some((p, t));
+                p += 1;
+                continue;
+            }
+            Some(token) if match_one(token, text[t]) => {
+                p += 1;
+                t += 1;
+                continue;
+            }
+            _ => {}
+        }
+
+        match star {
+            Some((sp, st)) => {
+                p = sp + 1;
+                t = st + 1;
+                star = Some((sp, st + 1));
+            }
+            None => return false,
+        }
+    }
+
+    tokens[p..].iter().all(|t| *t == Token::Star)
+}
+
+fn match_one(token: &Token, ch: char) -> bool {
+    match token {
+        Token::Char(c) => *c == ch,
+        Token::Any => true,
+        Token::Star => true,
+        Token::Class { negated, items } => items.iter().any(|i| match_class(i, ch)) != *negated,
+    }
+}
+
+fn match_class(item: &ClassItem, ch: char) -> bool {
+    match item {
+        ClassItem::Char(c) => *c == ch,
+        ClassItem::Range(lo, hi) => (*lo..=*hi).contains(&ch),
+        ClassItem::Named(name) => match name.as_str() {
+            "alpha" => ch.is_alphabetic(),
+            "digit" => ch.is_ascii_digit(),
+            "alnum" => ch.is_alphanumeric(),
+            "upper" => ch.is_uppercase(),
+            "lower" => ch.is_lowercase(),
+            "space" => ch.is_whitespace(),
+            "blank" => ch == ' ' || ch == '\t',
+            "punct" => ch.is_ascii_punctuation(),
+            "xdigit" => ch.is_ascii_hexdigit(),
+            "cntrl" => ch.is_control(),
+            "print" => !ch.is_control(),
+            "graph" => !ch.is_control() && !ch.is_whitespace(),
+            _ => false,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wildcards() {
+        assert!(matches("*.rs", "main.rs"));
+        assert!(!matches("*.rs", "main.rc"));
+        assert!(matches("a*b*c", "aXXbYYc"));
+        assert!(matches("?at", "cat"));
+        assert!(!matches("?at", "at"));
+        assert!(matches("*", ""));
+    }
+
+    #[test]
+    fn test_bracket() {
+        assert!(matches("[abc]x", "bx"));
+        assert!(!matches("[!abc]x", "bx"));
+        assert!(matches("[a-c][0-9]", "b7"));
+        assert!(matches("[]]", "]"));
+        assert!(matches("[[:digit:]]*", "1abc"));
+        assert!(matches("[unterminated", "[unterminated"));
+    }
+
+    #[test]
+    fn test_escape() {
+        assert!(matches("\\*", "*"));
+        assert!(!matches("\\*", "a"));
+        assert!(matches(&escape("a*[b]"), "a*[b]"));
+        assert!(has_magic("a*"));
+        assert!(!has_magic(&escape("a*?")));
+        assert_eq!("a*?", unescape(&escape("a*?")));
+    }
+}
@@ -0,0 +1,166 @@
+use std::ffi::{CStr, CString};
+
+use super::{WordPart, is_name};
+use crate::context::ShellContext;
+
+/// Replace tilde-prefixes in the unquoted parts of a word. A prefix starts a
+/// word, or in an assignment follows the `=` or any `:`, and runs up to the
+/// next `/` (or `:` in an assignment).
+pub fn expand(parts: &[WordPart], ctx: &ShellContext) -> Vec<WordPart> {
+    let assignment = is_assignment(parts);
+    let mut result = Vec::with_capacity(parts.len());
+    let mut seen_equals = false;
+
+    for (i, part) in parts.iter().enumerate() {
+        let WordPart::Literal(text) = part else {
+            result.push(part.clone());
+            continue;
+        };
+
+        // A prefix cut short by a quote or expansion isn't a tilde-prefix
+        let followed = i + 1 < parts.len();
+        let mut literal = String::new();
+        let mut rest = text.as_str();
+        let mut at_start = i == 0;
+
+        while !rest.is_empty() {
+            if at_start && rest.starts_with('~') {
+                let end = rest
+                    .find(|c| c == '/' || (assignment && c == ':'))
+                    .unwrap_or(rest.len());
+
+                if (end < rest.len() || !followed)
+                    && let Some(dir) = lookup(&rest[1..end], ctx)
+                {
+                    if !literal.is_empty() {
+                        result.push(WordPart::Literal(std::mem::take(&mut literal)));
+                    }
+                    result.push(WordPart::Quoted(dir));
+                    rest = &rest[end..];
+                    at_start = false;
+                    continue;
+                }
+            }
+
+            let ch = rest.chars().next().unwrap();
+            literal.push(ch);
+            rest = &rest[ch.len_utf8()..];
+            at_start = assignment && (ch == ':' || (ch == '=' && !seen_equals));
+            seen_equals |= ch == '=';
+        }
+
+        if !literal.is_empty() {
+            result.push(WordPart::Literal(literal));
+        }
+    }
+
+    result
+}
+
+// `NAME=...` where the name is unquoted
+fn is_assignment(parts: &[WordPart]) -> bool {
+    let Some(WordPart::Literal(text)) = parts.first() else {
+        return false;
+    };
+    let Some((name, _)) = text.split_once('=') else {
+        return false;
+    };
+
+    is_name(name)
+}
+
+fn lookup(user: &str, ctx: &ShellContext) -> Option<String> {
+    match user {
+        "" => ctx
+            .env("HOME")
+            .map(str::to_string)
+            .or_else(|| home_of(None)),
+        "+" => ctx.env("PWD").map(str::to_string),
+        "-" => ctx.env("OLDPWD").map(str::to_string),
+        user => home_of(Some(user)),
+    }
+}
+
+// Home directory from the passwd database, the current user's if `None`
+fn home_of(user: Option<&str>) -> Option<String> {
+    let entry = match user {
+        Some(user) => {
+            let name = CString::new(user).ok()?;
+            unsafe { libc::getpwnam(name.as_ptr()) }
+        }
+        None => unsafe { libc::getpwuid(libc::getuid()) },
+    };
+
+    if entry.is_null() {
+        return None;
+    }
+
+    let dir = unsafe { CStr::from_ptr((*entry).pw_dir) };
+    Some(dir.to_string_lossy().into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn literal(text: &str) -> WordPart {
+        WordPart::Literal(text.to_string())
+    }
+
+    fn quoted(text: &str) -> WordPart {
+        WordPart::Quoted(text.to_string())
+    }
+
+    #[test]
+    fn test_prefix() {
+        let mut ctx = ShellContext::new();
+        ctx.set_env("HOME", "/home/me");
+        ctx.set_env("OLDPWD", "/old");
+
+        assert_eq!(vec![quoted("/home/me")], expand(&[literal("~")], &ctx));
+        assert_eq!(
+            vec![quoted("/home/me"), literal("/src")],
+            expand(&[literal("~/src")], &ctx)
+        );
+        assert_eq!(vec![quoted("/old")], expand(&[literal("~-")], &ctx));
+        assert_eq!(vec![literal("a~")], expand(&[literal("a~")], &ctx));
+        assert_eq!(
+            vec![literal("~"), quoted("x")],
+            expand(&[literal("~"), quoted("x")], &ctx)
+        );
+        assert_eq!(
+            vec![literal("~no-such-user-here")],
+            expand(&[literal("~no-such-user-here")], &ctx)
+        );
+
+        // Whatever the passwd database on this host says, if anything
+        let root = unsafe { libc::getpwnam(c"root".as_ptr()) };
+        let expected = if root.is_null() {
+            literal("~root")
+        } else {
+            quoted(&unsafe { CStr::from_ptr((*root).pw_dir) }.to_string_lossy())
+        };
+        assert_eq!(vec![expected], expand(&[literal("~root")], &ctx));
+    }
+
+    #[test]
+    fn test_assignment() {
+        let mut ctx = ShellContext::new();
+        ctx.set_env("HOME", "/home/me");
+
+        assert_eq!(
+            vec![
+                literal("PATH="),
+                quoted("/home/me"),
+                literal("/bin:"),
+                quoted("/home/me"),
+                literal("/.local:/usr/bin"),
+            ],
+            expand(&[literal("PATH=~/bin:~/.local:/usr/bin")], &ctx)
+        );
+        assert_eq!(
+            vec![literal("--opt=~/x")],
+            expand(&[literal("--opt=~/x")], &ctx)
+        );
+    }
+}
@@ -0,0 +1,83 @@
+use super::{List, ShellContext, interrupted};
+use crate::expand::expand_word;
+use crate::parser::ast::{self, CompoundCommand};
+
+/// Run a compound command in the current shell, a subshell is expected to
+/// have been forked already.
+pub fn execute(command: &CompoundCommand, ctx: &mut ShellContext) -> i32 {
+    match command {
+        CompoundCommand::Brace(list) | CompoundCommand::Subshell(list) => run(list, ctx),
+        CompoundCommand::If {
+            branches,
+            otherwise,
+        } => {
+            for (condition, body) in branches {
+                if run_condition(condition, ctx) == 0 {
+                    return run(body, ctx);
+                }
+            }
+
+            otherwise.as_ref().map_or(0, |body| run(body, ctx))
+        }
+        CompoundCommand::While {
+            condition,
+            body,
+            until,
+        } => {
+            let mut status = 0;
+            loop {
+                let tested = run_condition(condition, ctx);
+                if interrupted(tested) {
+                    return tested;
+                }
+                if (tested == 0) == *until {
+                    break;
+                }
+
+                status = run(body, ctx);
+                if interrupted(status) {
+                    break;
+                }
+            }
+            status
+        }
+        CompoundCommand::For { name, words, body } => {
+            // There are no positional parameters to default to yet
+            let mut values = Vec::new();
+            for word in words.iter().flatten() {
+                match expand_word(word, ctx) {
+                    Ok(fields) => values.extend(fields),
+                    Err(e) => {
+                        eprintln!("{e}");
+                        return 1;
+                    }
+                }
+            }
+
+            let mut status = 0;
+            for value in values {
+                if let Err(e) = ctx.assign(name, &value) {
+                    eprintln!("{e}");
+                    return 1;
+                }
+                status = run(body, ctx);
+                if interrupted(status) {
+                    break;
+                }
+            }
+            status
+        }
+    }
+}
+
+fn run(list: &ast::List, ctx: &mut ShellContext) -> i32 {
+    List::new(list).run(ctx)
+}
+
+// A condition failing is no error, ERR isn't set off inside one
+fn run_condition(list: &ast::List, ctx: &mut ShellContext) -> i32 {
+    ctx.traps_mut().enter_condition();
+    let status = run(list, ctx);
+    ctx.traps_mut().leave_condition();
+    status
+}
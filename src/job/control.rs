@@ -0,0 +1,86 @@
+use crate::context::ShellContext;
+use crate::signal;
+
+use libc::{
+    SIG_IGN, SIGINT, SIGQUIT, SIGTSTP, SIGTTIN, SIGTTOU, fork, getpgrp, kill, setpgid, tcgetpgrp,
+    tcsetpgrp,
+};
+
+/// Set up the shell to run jobs in process groups of their own: wait until
+/// it is in the foreground, lead a group of its own, take the terminal and
+/// ignore the stop signals the terminal sends. Returns whether stdin is a
+/// terminal that job control can be done on.
+pub fn enable() -> bool {
+    unsafe {
+        // Started in the background of another shell, stop until resumed
+        loop {
+            match tcgetpgrp(0) {
+                -1 => return false,
+                pgid if pgid == getpgrp() => break,
+                _ => {
+                    kill(-getpgrp(), SIGTTIN);
+                }
+            }
+        }
+
+        // Sent by the terminal to stop a job, or one that isn't allowed to
+        // use it
+        for sig in [SIGTSTP, SIGTTIN, SIGTTOU] {
+            libc::signal(sig, SIG_IGN);
+        }
+
+        // Fails when the shell leads its session, and so a group, already
+        setpgid(0, 0);
+        tcsetpgrp(0, getpgrp()) == 0
+    }
+}
+
+/// Fork a process of a job. With job control the child gets the default
+/// signal dispositions back, joins process group `pgid`, or leads a new one
+/// when it's 0, and takes the terminal if the job runs in the `foreground`.
+/// Returns what `fork` does.
+pub fn fork_job(pgid: i32, foreground: bool, ctx: &mut ShellContext) -> i32 {
+    let control = ctx.job_control();
+
+    match unsafe { fork() } {
+        0 => {
+            if control {
+                // Still ignoring SIGTTOU, which taking the terminal from the
+                // background sends before the parent has handed it over
+                unsafe {
+                    setpgid(0, pgid);
+                    if foreground {
+                        tcsetpgrp(0, getpgrp());
+                    }
+                }
+                signal::reset_dispositions();
+            } else if !foreground {
+                // Without process groups, keys typed at the terminal would
+                // reach background jobs too
+                for sig in [SIGINT, SIGQUIT] {
+                    unsafe { libc::signal(sig, SIG_IGN) };
+                }
+            }
+
+            ctx.enter_subshell();
+            0
+        }
+        pid => {
+            // Set from both sides, whichever runs first wins the race
+            if control && pid > 0 {
+                unsafe { setpgid(pid, if pgid == 0 { pid } else { pgid }) };
+            }
+            pid
+        }
+    }
+}
+
+/// Make `pgid` the foreground process group of the terminal.
+pub fn give_terminal(pgid: i32) {
+    unsafe { tcsetpgrp(0, pgid) };
+}
+
+/// Put the shell back in the foreground of the terminal.
+pub fn take_terminal() {
+    unsafe { tcsetpgrp(0, getpgrp()) };
+}
@@ -0,0 +1,268 @@
+use super::{BackgroundJob, Job, ShellContext, control, interrupted};
+use crate::lexer::Lexer;
+use crate::parser::{
+    self,
+    ast::{self, AndOr, Command, CompoundCommand, Connector},
+};
+use crate::trap::{self, Condition};
+
+use std::io;
+
+/// Runs and-or lists separated by `;`, `&` and newlines, left to right.
+pub struct List<'a> {
+    list: &'a ast::List,
+}
+
+impl<'a> List<'a> {
+    pub fn new(list: &'a ast::List) -> List<'a> {
+        List { list }
+    }
+
+    /// Run each and-or list in turn, returning the status of the last one.
+    pub fn run(&self, ctx: &mut ShellContext) -> i32 {
+        let mut status = 0;
+
+        for and_or in self.list.items.iter() {
+            status = match and_or.background {
+                true => Self::spawn(and_or, ctx),
+                false => Self::run_and_or(and_or, ctx),
+            };
+            if interrupted(status) && !and_or.background {
+                break;
+            }
+        }
+
+        status
+    }
+
+    /// Parse and run `source`, e.g. the action of a trap, returning 2 if it
+    /// isn't valid.
+    pub fn run_source(source: &str, ctx: &mut ShellContext) -> i32 {
+        let mut lex = Lexer::new();
+        lex.push(source);
+        let parsed = lex
+            .tokenize()
+            .map_err(|e| e.to_string())
+            .and_then(|tokens| parser::parse(tokens).map_err(|e| e.to_string()));
+
+        match parsed {
+            Ok(list) => List::new(&list).run(ctx),
+            Err(e) => {
+                eprintln!("{e}");
+                2
+            }
+        }
+    }
+
+    // Run each pipeline whose connector allows it, returning the status of
+    // the last one that ran
+    fn run_and_or(and_or: &AndOr, ctx: &mut ShellContext) -> i32 {
+        let mut status = 0;
+        let mut ran_last = false;
+
+        for (i, (connector, pipeline)) in and_or.pipelines.iter().enumerate() {
+            let skip = match connector {
+                Connector::Always => false,
+                Connector::And => status != 0,
+                Connector::Or => status == 0,
+            };
+            if skip {
+                continue;
+            }
+
+            // Anything `&&` or `||` tests is allowed to fail
+            ran_last = i + 1 == and_or.pipelines.len();
+            if !ran_last {
+                ctx.traps_mut().enter_condition();
+            }
+            status = Job::new(pipeline).run(ctx);
+            if !ran_last {
+                ctx.traps_mut().leave_condition();
+            }
+            if interrupted(status) {
+                return status;
+            }
+        }
+
+        // Commands inside a compound command have set off ERR already
+        let compound = matches!(
+            and_or.pipelines.last().map(|(_, p)| p.commands.as_slice()),
+            Some([Command::Compound(command, _)]) if !matches!(command, CompoundCommand::Subshell(_))
+        );
+        if status != 0 && ran_last && !compound {
+            trap::run(Condition::Err, ctx);
+        }
+
+        status
+    }
+
+    // Start the and-or list in the background and add it to the job table. A
+    // lone pipeline is forked directly, anything longer in a subshell.
+    fn spawn(and_or: &AndOr, ctx: &mut ShellContext) -> i32 {
+        let job = match and_or.pipelines.as_slice() {
+            [(_, pipeline)] => match Job::new(pipeline).spawn(ctx) {
+                Some(job) => job,
+                None => {
+                    ctx.set_status(1);
+                    return 1;
+                }
+            },
+            _ => match control::fork_job(0, false, ctx) {
+                0 => {
+                    let status = Self::run_and_or(and_or, ctx);
+                    let _ = io::Write::flush(&mut io::stdout());
+                    std::process::exit(status);
+                }
+                pid => {
+                    let pgid = if ctx.job_control() { pid } else { 0 };
+                    BackgroundJob::new(vec![pid], pgid, and_or.to_string())
+                }
+            },
+        };
+
+        let pid = job.pids.last().copied().unwrap_or(0);
+        let jobs = ctx.jobs_mut();
+        jobs.set_last_pid(pid);
+        let id = jobs.add(job);
+        eprintln!("[{id}] {pid}");
+
+        ctx.set_status(0);
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::job::JobState;
+    use crate::lexer::Lexer;
+    use crate::parser;
+
+    use std::fs;
+    use std::path::PathBuf;
+
+    fn run(input: &str, ctx: &mut ShellContext) -> i32 {
+        let mut lex = Lexer::new();
+        lex.push(input);
+
+        List::new(&parser::parse(lex.tokenize().unwrap()).unwrap()).run(ctx)
+    }
+
+    // A scratch file that is removed once the test is done, failed or not
+    struct TempPath(PathBuf);
+
+    impl TempPath {
+        fn new(name: &str) -> Self {
+            Self(std::env::temp_dir().join(format!("{name}-{}", std::process::id())))
+        }
+    }
+
+    impl Drop for TempPath {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    #[test]
+    fn test_short_circuit() {
+        let mut ctx = ShellContext::new();
+
+        assert_eq!(0, run("let 0 && let a=1 || let b=2; let c=$?+5", &mut ctx));
+        assert_eq!(None, ctx.env("a"));
+        assert_eq!(Some("2"), ctx.env("b"));
+        assert_eq!(Some("5"), ctx.env("c"));
+
+        run("let 0", &mut ctx);
+        assert_eq!(1, ctx.status());
+    }
+
+    #[test]
+    fn test_compound() {
+        let mut ctx = ShellContext::new();
+
+        run(
+            "if let 0; then x=a; elif let 1; then x=b; else x=c; fi",
+            &mut ctx,
+        );
+        assert_eq!(Some("b"), ctx.env("x"));
+
+        run(
+            "n=0; s=; while (( n < 3 )); do s=$s$n; (( n++ )); done",
+            &mut ctx,
+        );
+        assert_eq!(Some("012"), ctx.env("s"));
+
+        run("s=; for w in a 'b c' d; do s=$s-$w; done", &mut ctx);
+        assert_eq!(Some("-a-b c-d"), ctx.env("s"));
+
+        run(
+            "until (( n == 0 )); do (( n-- )); done; { y=1; z=$n; }",
+            &mut ctx,
+        );
+        assert_eq!(Some("0"), ctx.env("z"));
+    }
+
+    #[test]
+    fn test_subshell() {
+        let mut ctx = ShellContext::new();
+
+        assert_eq!(3, run("x=$(exit 3)", &mut ctx));
+        assert_eq!(1, run("x=$(exit 2) y=$(false)", &mut ctx));
+        assert_eq!(0, run("x=$(false) true", &mut ctx));
+        assert_eq!(0, run("x=1", &mut ctx));
+
+        // Only the shell itself writes its history out
+        let path = TempPath::new("subshell");
+        ctx.set_env("HISTFILE", &path.0.to_string_lossy());
+        ctx.push_history("x=1");
+        run("(exit 3); x=$(exit 4)", &mut ctx);
+        assert!(!path.0.exists());
+    }
+
+    #[test]
+    fn test_redirect() {
+        let mut ctx = ShellContext::new();
+        let out = TempPath::new("redirect-out");
+        let copy = TempPath::new("redirect-copy");
+        let source = format!("printf hi 3>&- 3>{} >&3", out.0.display());
+        let copied = format!("cat 3<&- 3<{} <&3 >{}", out.0.display(), copy.0.display());
+
+        // Closing 3 first makes it the fd `open` hands out
+        assert_eq!(0, run(&source, &mut ctx));
+        assert_eq!(0, run(&copied, &mut ctx));
+        assert_eq!("hi", fs::read_to_string(&copy.0).unwrap());
+    }
+
+    #[test]
+    fn test_interrupt() {
+        let mut ctx = ShellContext::new();
+
+        // Killed by SIGINT like a job interrupted from the terminal
+        let source = "n=0; while true; do ((n++)); sh -c 'kill -INT $$'; done; n=x";
+        assert_eq!(130, run(source, &mut ctx));
+        assert_eq!(Some("1"), ctx.env("n"));
+
+        run(
+            "for i in 1 2; do sh -c 'kill -INT $$' || i=x; done",
+            &mut ctx,
+        );
+        assert_eq!(Some("1"), ctx.env("i"));
+    }
+
+    #[test]
+    fn test_background() {
+        let mut ctx = ShellContext::new();
+
+        // The assignment happens in the child, not the shell
+        assert_eq!(0, run("x=1 && exit 3 &", &mut ctx));
+        assert_eq!(None, ctx.env("x"));
+        assert!(ctx.jobs().last_pid().is_some());
+
+        while ctx.jobs().iter().any(|job| job.state == JobState::Running) {
+            ctx.jobs_mut().update();
+        }
+        let done = ctx.jobs_mut().remove_done();
+        assert_eq!("x=1 && exit 3", done[0].1.command);
+        assert_eq!(JobState::Done(3), done[0].1.state);
+    }
+}
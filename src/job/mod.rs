@@ -1,87 +1,190 @@
 use super::ShellContext;
-use super::lexer::Token;
+use crate::context::ShellOption;
+use crate::parser::ast::Pipeline;
 
-use libc::{dup2, fork, waitpid};
-use std::path::PathBuf;
+use crate::signal::{self, IgnoreGuard};
+use crate::trap::{self, Condition};
+
+use libc::{SIGINT, SIGPIPE, SIGTSTP, WEXITSTATUS, WIFSIGNALED, WTERMSIG, dup2};
 use std::{io, os::fd::AsRawFd};
 
+mod compound;
+mod control;
+mod list;
 mod process;
 mod redirect;
+mod table;
 
+pub use control::enable as enable_job_control;
+pub use list::List;
 use process::Process;
-use redirect::{FdGuard, Redirect};
+use redirect::FdGuard;
+pub use table::{BackgroundJob, JobState, JobTable};
 
-pub struct Job {
-    processes: Vec<Process>,
+/// Whether `status` is that of a job interrupted from the terminal, which
+/// stops the rest of the list or loop it's part of too.
+fn interrupted(status: i32) -> bool {
+    status == 128 + SIGINT
 }
 
-impl Job {
-    pub fn new(tokens: Vec<Token>) -> Job {
-        let mut processes = Vec::<Process>::new();
+pub struct Job<'a> {
+    pipeline: &'a Pipeline,
+    processes: Vec<Process<'a>>,
+}
 
-        let mut current_process = Process::new();
-        let mut iter = tokens.into_iter().peekable();
-        while let Some(tk) = iter.next() {
-            match tk {
-                Token::Literal(arg) => {
-                    current_process.push_arg(arg);
-                }
-                // Token::Complex(arg) => {
-                //     todo!()
-                // }
-                Token::Write(fd) => {
-                    let Some(Token::Literal(target)) = iter.peek() else {
-                        todo!()
-                    };
-                    current_process.push_output(Redirect::Write {
-                        fd,
-                        target: PathBuf::from(target),
-                    });
-                    iter.next();
-                }
-                Token::Append(fd) => {
-                    let Some(Token::Literal(target)) = iter.peek() else {
-                        todo!()
-                    };
-                    current_process.push_output(Redirect::Append {
-                        fd,
-                        target: PathBuf::from(target),
-                    });
-                    iter.next();
-                }
-                Token::Pipe => {
-                    processes.push(current_process);
-                    current_process = Process::new();
-                }
-            }
+impl<'a> Job<'a> {
+    pub fn new(pipeline: &'a Pipeline) -> Job<'a> {
+        let processes = pipeline.commands.iter().map(Process::new).collect();
+
+        Job {
+            pipeline,
+            processes,
         }
+    }
+
+    pub fn processes(&self) -> impl Iterator<Item = &Process<'a>> {
+        self.processes.iter()
+    }
+
+    fn runs_in_shell(&self, argvs: &[Vec<String>], ctx: &ShellContext) -> bool {
+        self.processes.len() == 1 && self.processes[0].runs_in_shell(&argvs[0], ctx)
+    }
 
-        // TODO a condition check
-        processes.push(current_process);
+    /// Run the pipeline, recording its status as `$?` and the status of each
+    /// stage as `PIPESTATUS`.
+    pub fn run(&self, ctx: &mut ShellContext) -> i32 {
+        trap::run(Condition::Debug, ctx);
 
-        Job { processes }
+        let statuses = self.execute(ctx);
+        Self::record_status(statuses, ctx)
     }
 
-    pub fn processes(&self) -> impl Iterator<Item = &Process> {
-        self.processes.iter()
+    /// Bring a job from the table back to the foreground, continuing it, and
+    /// wait for it like a pipeline that was just started.
+    pub fn foreground(job: BackgroundJob, ctx: &mut ShellContext) -> i32 {
+        let statuses = Self::wait(job, true, ctx);
+        Self::record_status(statuses, ctx)
     }
 
-    fn is_single_builtin(&self, ctx: &ShellContext) -> bool {
-        self.processes.len() == 1 && self.processes[0].is_builtin(ctx)
+    fn record_status(statuses: Vec<i32>, ctx: &mut ShellContext) -> i32 {
+        // With pipefail the rightmost failing stage decides the status
+        let status = match ctx.option(ShellOption::Pipefail) {
+            true => statuses.iter().rev().find(|s| **s != 0),
+            false => statuses.last(),
+        };
+        let status = status.copied().unwrap_or(0);
+
+        ctx.set_status(status);
+        ctx.set_pipestatus(statuses);
+
+        // Signals that came in while the job ran
+        trap::run_pending(ctx);
+        status
     }
 
-    pub fn run(&self, ctx: &mut ShellContext) {
-        if self.is_single_builtin(ctx) {
-            // Run single builtin without forking the process
-            self.processes[0].execute(ctx);
-            return;
+    /// Start the pipeline in the background, every stage in a child of its
+    /// own even for builtins, without waiting for it.
+    pub fn spawn(&self, ctx: &mut ShellContext) -> Option<BackgroundJob> {
+        let argvs = self.expand_argvs(ctx)?;
+
+        Some(self.fork(&argvs, false, ctx))
+    }
+
+    // Status of each process, in order
+    fn execute(&self, ctx: &mut ShellContext) -> Vec<i32> {
+        // Only substitutions in this command count for its status
+        ctx.take_substitution_status();
+
+        let Some(argvs) = self.expand_argvs(ctx) else {
+            return vec![1];
+        };
+
+        if self.runs_in_shell(&argvs, ctx) {
+            // Run builtins and compound commands without forking the process
+            return vec![self.processes[0].execute(&argvs[0], ctx)];
         }
 
+        let job = self.fork(&argvs, true, ctx);
+        Self::wait(job, false, ctx)
+    }
+
+    // Wait for a job that has the terminal until it exits or stops, a stopped
+    // job goes (back) into the job table
+    fn wait(mut job: BackgroundJob, resume: bool, ctx: &mut ShellContext) -> Vec<i32> {
+        // Keystrokes meant for the job mustn't take down the shell
+        let _ignore = IgnoreGuard::new(&signal::JOB_SIGNALS);
+
+        let control = ctx.job_control() && job.pgid != 0;
+        if control {
+            control::give_terminal(job.pgid);
+        }
+        if resume {
+            job.resume();
+        }
+
+        job.wait(true);
+        if control {
+            control::take_terminal();
+        }
+
+        // Processes still around are stopped
+        let statuses = job
+            .statuses
+            .iter()
+            .map(|status| status.map_or(128 + SIGTSTP, Self::decode_status))
+            .collect();
+
+        match job.state {
+            JobState::Stopped => {
+                let jobs = ctx.jobs_mut();
+                let id = jobs.add(job);
+                if let Some(job) = jobs.iter().find(|job| job.id == id) {
+                    eprintln!("\n{}", job.describe(jobs.marker(id), false));
+                }
+            }
+            // Starts a line of its own after the `^C` the terminal echoes
+            JobState::Killed { signal: SIGINT, .. } => eprintln!("\n{}", job.state),
+            // A reader going away is how pipelines normally end early
+            JobState::Killed {
+                signal: SIGPIPE, ..
+            } => {}
+            JobState::Killed { .. } => eprintln!("{}", job.state),
+            _ => {}
+        }
+
+        statuses
+    }
+
+    // Expand words in the shell itself so every stage sees the same state,
+    // an expansion error aborts the whole job
+    fn expand_argvs(&self, ctx: &mut ShellContext) -> Option<Vec<Vec<String>>> {
+        match self
+            .processes()
+            .map(|p| p.expand_argv(ctx))
+            .collect::<Result<Vec<_>, _>>()
+        {
+            Ok(argvs) => Some(argvs),
+            Err(e) => {
+                eprintln!("{e}");
+                None
+            }
+        }
+    }
+
+    // Fork every stage with pipes between them, in a process group of their
+    // own with job control
+    fn fork(
+        &self,
+        argvs: &[Vec<String>],
+        foreground: bool,
+        ctx: &mut ShellContext,
+    ) -> BackgroundJob {
         let mut pids = Vec::new();
+        let mut pgid = 0;
         let mut prev_read: Option<io::PipeReader> = None;
 
         // Run multiple process pipeline || single process external binary
-        for (i, process) in self.processes().enumerate() {
+        for (i, (process, argv)) in self.processes().zip(argvs.iter()).enumerate() {
             let is_last = i == self.processes.len() - 1;
 
             // Create pipe
@@ -90,7 +193,7 @@ impl Job {
                 next_pipe = Some(io::pipe().expect("pipe failed"));
             }
 
-            match unsafe { fork() } {
+            match control::fork_job(pgid, foreground, ctx) {
                 0 => {
                     // Read from prev pipe
                     if let Some(ref prev) = prev_read {
@@ -108,13 +211,17 @@ impl Job {
                     drop(prev_read);
                     drop(next_pipe);
 
-                    process.execute(ctx);
-                    std::process::exit(1);
+                    let status = process.execute(argv, ctx);
+                    let _ = io::Write::flush(&mut io::stdout());
+                    std::process::exit(status);
                 }
 
                 pid => {
-                    // Parent collect pid
+                    // Parent collect pid, the first one leads the group
                     pids.push(pid);
+                    if ctx.job_control() && pgid == 0 {
+                        pgid = pid;
+                    }
 
                     // Parent doesn't need to read from prev read end
                     drop(prev_read);
@@ -125,9 +232,15 @@ impl Job {
             }
         }
 
-        // Wait for all pid
-        for pid in pids {
-            unsafe { waitpid(pid, std::ptr::null_mut(), 0) };
+        BackgroundJob::new(pids, pgid, self.pipeline.to_string())
+    }
+
+    // Exit code of a normal exit, 128 + signal number when killed
+    fn decode_status(status: i32) -> i32 {
+        if WIFSIGNALED(status) {
+            128 + WTERMSIG(status)
+        } else {
+            WEXITSTATUS(status)
         }
     }
 }
@@ -1,64 +1,157 @@
-use std::os::fd::AsRawFd;
-use std::{fs::File, io};
+use std::io::{Seek, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::{fs, fs::File, io};
 
+use super::FdGuard;
 use super::ShellContext;
-use super::{FdGuard, Redirect};
+use super::compound;
 
 use crate::commands::ExtCommand;
+use crate::expand::{ExpandError, Word, expand_string, expand_word};
+use crate::parser::ast::{Assignment, Command, CompoundCommand, Redirect};
 
-pub struct Process {
-    argv: Vec<String>,
-    redirects: Vec<Redirect>,
+pub struct Process<'a> {
+    command: &'a Command,
 }
 
-impl Process {
-    pub fn new() -> Process {
-        Process {
-            argv: Vec::<String>::new(),
-            redirects: Vec::<Redirect>::new(),
+impl<'a> Process<'a> {
+    pub fn new(command: &'a Command) -> Process<'a> {
+        Process { command }
+    }
+
+    // Compound commands expand their words as they run
+    pub fn expand_argv(&self, ctx: &mut ShellContext) -> Result<Vec<String>, ExpandError> {
+        let mut argv = Vec::new();
+
+        if let Command::Simple(simple) = self.command {
+            for word in simple.words.iter() {
+                argv.extend(expand_word(word, ctx)?);
+            }
         }
+
+        Ok(argv)
     }
 
-    pub fn push_arg(&mut self, arg: String) {
-        self.argv.push(arg);
+    pub fn is_builtin(argv: &[String], ctx: &ShellContext) -> bool {
+        argv.first()
+            .map(|n| ctx.registry().check_builtin(n))
+            .unwrap_or(false)
     }
 
-    pub fn push_output(&mut self, output: Redirect) {
-        self.redirects.push(output);
+    /// Whether the shell runs this itself when it's alone in a pipeline,
+    /// rather than forking.
+    pub fn runs_in_shell(&self, argv: &[String], ctx: &ShellContext) -> bool {
+        match self.command {
+            Command::Simple(_) => argv.is_empty() || Self::is_builtin(argv, ctx),
+            Command::Compound(CompoundCommand::Subshell(_), _) => false,
+            Command::Compound(..) => true,
+        }
     }
 
-    pub fn name(&self) -> Option<&String> {
-        self.argv.get(0)
+    fn redirects(&self) -> &'a [Redirect] {
+        match self.command {
+            Command::Simple(simple) => &simple.redirects,
+            Command::Compound(_, redirects) => redirects,
+        }
     }
 
-    pub fn args(&self) -> &[String] {
-        self.argv.get(1..).unwrap_or(&[])
+    fn expand_target(target: &Word, ctx: &mut ShellContext) -> io::Result<String> {
+        expand_string(target, ctx).map_err(io::Error::other)
     }
 
-    pub fn is_builtin(&self, ctx: &ShellContext) -> bool {
-        self.name()
-            .map(|n| ctx.registry().check_builtin(n))
-            .unwrap_or(false)
+    // Stage here-document text in an unlinked temporary file so it can be
+    // read from an ordinary fd regardless of its size
+    fn here_document(text: &str) -> io::Result<File> {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+        let path = std::env::temp_dir().join(format!(
+            "sh-heredoc-{}-{}",
+            std::process::id(),
+            COUNT.fetch_add(1, Ordering::Relaxed)
+        ));
+        let mut file = File::options()
+            .create_new(true)
+            .read(true)
+            .write(true)
+            .open(&path)?;
+        fs::remove_file(&path)?;
+
+        file.write_all(text.as_bytes())?;
+        file.rewind()?;
+        Ok(file)
     }
 
-    fn apply_redirects(&self) -> io::Result<FdGuard> {
+    fn apply_redirects(&self, ctx: &mut ShellContext) -> io::Result<FdGuard> {
         let mut guard = FdGuard::new();
 
-        for r in self.redirects.iter() {
+        for r in self.redirects().iter() {
             match r {
                 Redirect::Write { fd, target } => {
                     let file = File::options()
                         .create(true)
                         .truncate(true)
                         .write(true)
-                        .open(target)?;
+                        .open(Self::expand_target(target, ctx)?)?;
 
-                    guard.redirect(file.as_raw_fd(), *fd)?
+                    guard.redirect_file(file, *fd)?
                 }
                 Redirect::Append { fd, target } => {
-                    let file = File::options().create(true).append(true).open(target)?;
+                    let file = File::options()
+                        .create(true)
+                        .append(true)
+                        .open(Self::expand_target(target, ctx)?)?;
+
+                    guard.redirect_file(file, *fd)?
+                }
+                Redirect::Read { fd, target } => {
+                    let file = File::open(Self::expand_target(target, ctx)?)?;
+
+                    guard.redirect_file(file, *fd)?
+                }
+                Redirect::Duplicate { fd, source } => {
+                    let source = Self::expand_target(source, ctx)?;
+                    match source.as_str() {
+                        "-" => guard.close(*fd)?,
+                        _ => {
+                            let Ok(src) = source.parse::<i32>() else {
+                                return Err(io::Error::other(format!(
+                                    "{source}: ambiguous redirect"
+                                )));
+                            };
+                            guard.redirect(src, *fd)?
+                        }
+                    }
+                }
+                Redirect::Close { fd } => guard.close(*fd)?,
+                Redirect::WriteAll { target } => {
+                    let file = File::options()
+                        .create(true)
+                        .truncate(true)
+                        .write(true)
+                        .open(Self::expand_target(target, ctx)?)?;
 
-                    guard.redirect(file.as_raw_fd(), *fd)?
+                    guard.redirect_file(file, 1)?;
+                    guard.redirect(1, 2)?
+                }
+                Redirect::AppendAll { target } => {
+                    let file = File::options()
+                        .create(true)
+                        .append(true)
+                        .open(Self::expand_target(target, ctx)?)?;
+
+                    guard.redirect_file(file, 1)?;
+                    guard.redirect(1, 2)?
+                }
+                Redirect::HereDoc { fd, body } => {
+                    let file = Self::here_document(&Self::expand_target(body, ctx)?)?;
+
+                    guard.redirect_file(file, *fd)?
+                }
+                Redirect::HereString { fd, word } => {
+                    let text = Self::expand_target(word, ctx)? + "\n";
+                    let file = Self::here_document(&text)?;
+
+                    guard.redirect_file(file, *fd)?
                 }
             }
         }
@@ -66,22 +159,72 @@ impl Process {
         Ok(guard)
     }
 
-    pub fn execute(&self, ctx: &mut ShellContext) {
+    pub fn execute(&self, argv: &[String], ctx: &mut ShellContext) -> i32 {
         // Do some redirecting stuff
-        let _fd_guard = match self.apply_redirects() {
+        let _fd_guard = match self.apply_redirects(ctx) {
             Ok(g) => g,
             Err(e) => {
                 eprintln!("failed to redirect: {e}");
-                return;
+                return 1;
             }
         };
 
-        let (name, args) = self.argv.split_first().unwrap();
+        let simple = match self.command {
+            Command::Simple(simple) => simple,
+            Command::Compound(compound, _) => return compound::execute(compound, ctx),
+        };
+
+        let assignments = match Self::expand_assignments(&simple.assignments, ctx) {
+            Ok(a) => a,
+            Err(e) => {
+                eprintln!("{e}");
+                return 1;
+            }
+        };
+
+        // Not even for the one command
+        if let Some((name, _)) = assignments.iter().find(|(name, _)| ctx.is_readonly(name)) {
+            eprintln!("{name}: readonly variable");
+            return 1;
+        }
+
+        // Nothing left to run, e.g. `> file`, `NAME=value` or an empty expansion,
+        // assignments then stay in the shell and the status is that of the
+        // last command substitution
+        let Some((name, args)) = argv.split_first() else {
+            for (name, value) in assignments {
+                ctx.set_env(&name, &value);
+            }
+            return ctx.take_substitution_status().unwrap_or(0);
+        };
 
         if let Some(builtin) = ctx.registry().get_command(name) {
-            builtin.execute(args, ctx);
+            // Assignments only last for the builtin
+            let saved: Vec<_> = assignments
+                .iter()
+                .map(|(name, value)| (name, ctx.set_env(name, value)))
+                .collect();
+            let status = builtin.execute(args, ctx);
+
+            for (name, old) in saved.into_iter().rev() {
+                match old {
+                    Some(old) => ctx.set_env(name, &old),
+                    None => ctx.unset_env(name),
+                };
+            }
+            status
         } else {
-            ExtCommand::execute(name, args, ctx);
+            ExtCommand::execute(name, args, &assignments, ctx)
         }
     }
+
+    fn expand_assignments(
+        assignments: &[Assignment],
+        ctx: &mut ShellContext,
+    ) -> Result<Vec<(String, String)>, ExpandError> {
+        assignments
+            .iter()
+            .map(|a| Ok((a.name.clone(), expand_string(&a.value, ctx)?)))
+            .collect()
+    }
 }
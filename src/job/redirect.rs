@@ -1,14 +1,15 @@
-use std::{collections::HashMap, io, path::PathBuf};
+use std::fs::File;
+use std::os::fd::{AsRawFd, IntoRawFd};
+use std::{collections::HashMap, io};
 
-use libc::{close, dup, dup2};
+use libc::{F_DUPFD_CLOEXEC, F_SETFD, close, dup2, fcntl};
 
-pub enum Redirect {
-    Write { fd: i32, target: PathBuf },
-    Append { fd: i32, target: PathBuf },
-}
+// Keep backups out of the way of fds users are likely to name
+const BACKUP_FD_MIN: i32 = 10;
 
 pub struct FdGuard {
-    saved: HashMap<i32, i32>,
+    // Backup of each redirected fd, `None` if it wasn't open to begin with
+    saved: HashMap<i32, Option<i32>>,
 }
 
 impl FdGuard {
@@ -24,13 +25,19 @@ impl FdGuard {
             return Ok(());
         }
 
-        // Create fd backup
-        let backup = unsafe { dup(fd) };
+        // Create fd backup, close-on-exec so children never see it
+        let backup = unsafe { fcntl(fd, F_DUPFD_CLOEXEC, BACKUP_FD_MIN) };
         if backup < 0 {
-            return Err(io::Error::last_os_error());
+            let err = io::Error::last_os_error();
+            if err.raw_os_error() != Some(libc::EBADF) {
+                return Err(err);
+            }
+
+            self.saved.insert(fd, None);
+            return Ok(());
         }
 
-        self.saved.insert(fd, backup);
+        self.saved.insert(fd, Some(backup));
         Ok(())
     }
 
@@ -43,6 +50,33 @@ impl FdGuard {
 
         Ok(())
     }
+
+    // `open` hands out the lowest free fd, so `file` may already be `dst`
+    // when `dst` wasn't open. It's kept then and closed again on drop.
+    pub fn redirect_file(&mut self, file: File, dst: i32) -> io::Result<()> {
+        if file.as_raw_fd() != dst {
+            return self.redirect(file.as_raw_fd(), dst);
+        }
+
+        let fd = file.into_raw_fd();
+        self.saved.entry(fd).or_insert(None);
+
+        // Unlike `dup2`, nothing clears close-on-exec here
+        if unsafe { fcntl(fd, F_SETFD, 0) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(())
+    }
+
+    pub fn close(&mut self, fd: i32) -> io::Result<()> {
+        self.save(fd)?;
+
+        // Closing an fd that isn't open is not an error
+        unsafe { close(fd) };
+
+        Ok(())
+    }
 }
 
 impl Drop for FdGuard {
@@ -51,8 +85,15 @@ impl Drop for FdGuard {
             // Redirect back and close the backup
             unsafe {
                 // Ignore errors during restoration (best-effort cleanup)
-                dup2(backup, fd);
-                close(backup);
+                match backup {
+                    Some(backup) => {
+                        dup2(backup, fd);
+                        close(backup);
+                    }
+                    None => {
+                        close(fd);
+                    }
+                }
             }
         }
     }
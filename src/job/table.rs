@@ -0,0 +1,401 @@
+use crate::signal;
+
+use libc::{
+    SIGCONT, SIGHUP, SIGTSTP, WCONTINUED, WCOREDUMP, WEXITSTATUS, WIFCONTINUED, WIFSIGNALED,
+    WIFSTOPPED, WNOHANG, WTERMSIG, WUNTRACED, c_int, kill, waitpid,
+};
+use std::fmt;
+
+/// Jobs started in the background or stopped, by number.
+#[derive(Default)]
+pub struct JobTable {
+    jobs: Vec<BackgroundJob>,
+    // Job numbers from the least to the most recently started or stopped, the
+    // last one is the current job, `%+`, and the one before it `%-`
+    recent: Vec<usize>,
+    // `$!`, the last process of the most recent background job
+    last_pid: Option<i32>,
+    // Whether exiting was held off once because of stopped jobs
+    warned_stopped: bool,
+}
+
+pub struct BackgroundJob {
+    pub id: usize,
+    // Process group of the job, 0 when it shares the shell's
+    pub pgid: i32,
+    pub pids: Vec<i32>,
+    // The command as written, shown by `jobs`
+    pub command: String,
+    pub state: JobState,
+    // Left running when the shell exits, set with `disown -h`
+    pub nohup: bool,
+    // Wait status of each process once it has been reaped
+    pub(super) statuses: Vec<Option<i32>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum JobState {
+    Running,
+    Stopped,
+    // Every process has exited, with the status of the last one
+    Done(i32),
+    // Every process has exited and a signal killed the last one
+    Killed { signal: c_int, core_dumped: bool },
+}
+
+impl fmt::Display for JobState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JobState::Running => write!(f, "Running"),
+            JobState::Stopped => write!(f, "Stopped"),
+            JobState::Done(0) => write!(f, "Done"),
+            JobState::Done(status) => write!(f, "Exit {status}"),
+            JobState::Killed {
+                signal,
+                core_dumped,
+            } => {
+                write!(f, "{}", signal::describe(*signal))?;
+                match core_dumped {
+                    true => write!(f, " (core dumped)"),
+                    false => Ok(()),
+                }
+            }
+        }
+    }
+}
+
+impl JobState {
+    pub fn is_finished(&self) -> bool {
+        matches!(self, JobState::Done(_) | JobState::Killed { .. })
+    }
+
+    /// The status a job in this state has as `$?`, none while it's running.
+    pub fn status(&self) -> Option<i32> {
+        match self {
+            JobState::Running => None,
+            JobState::Stopped => Some(128 + SIGTSTP),
+            JobState::Done(status) => Some(*status),
+            JobState::Killed { signal, .. } => Some(128 + signal),
+        }
+    }
+}
+
+impl JobTable {
+    /// Add a job to the table as the current job and return its number. A job
+    /// that had a number before keeps it.
+    pub fn add(&mut self, mut job: BackgroundJob) -> usize {
+        if job.id == 0 {
+            // Numbers are reused once the highest jobs are gone
+            job.id = self.jobs.last().map_or(0, |last| last.id) + 1;
+        }
+
+        let id = job.id;
+        let index = self.jobs.partition_point(|other| other.id < id);
+        self.jobs.insert(index, job);
+        self.recent.push(id);
+
+        id
+    }
+
+    /// Take a job out of the table.
+    pub fn remove(&mut self, id: usize) -> Option<BackgroundJob> {
+        let index = self.jobs.iter().position(|job| job.id == id)?;
+        self.recent.retain(|other| *other != id);
+
+        Some(self.jobs.remove(index))
+    }
+
+    pub fn get_mut(&mut self, id: usize) -> Option<&mut BackgroundJob> {
+        self.jobs.iter_mut().find(|job| job.id == id)
+    }
+
+    /// The number of the job that process `pid` is part of.
+    pub fn find_pid(&self, pid: i32) -> Option<usize> {
+        let job = self.jobs.iter().find(|job| job.pids.contains(&pid))?;
+        Some(job.id)
+    }
+
+    pub fn last_pid(&self) -> Option<i32> {
+        self.last_pid
+    }
+
+    pub fn set_last_pid(&mut self, pid: i32) {
+        self.last_pid = Some(pid);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &BackgroundJob> {
+        self.jobs.iter()
+    }
+
+    /// `+` for the current job, `-` for the previous one.
+    pub fn marker(&self, id: usize) -> char {
+        let mut ids = self.recent.iter().rev();
+
+        match (ids.next(), ids.next()) {
+            (Some(current), _) if *current == id => '+',
+            (_, Some(previous)) if *previous == id => '-',
+            _ => ' ',
+        }
+    }
+
+    /// Find the job named by a job spec: `%n`, `%+` or `%%` for the current
+    /// job, `%-` for the previous one, `%string` for the job whose command
+    /// starts with `string` and `%?string` for one that contains it.
+    pub fn find(&self, spec: &str) -> Result<usize, String> {
+        let no_such_job = || format!("{spec}: no such job");
+        let text = spec.strip_prefix('%').unwrap_or(spec);
+
+        let recent = |back: usize| {
+            let index = self.recent.len().checked_sub(back + 1);
+            index.map(|i| self.recent[i]).ok_or_else(no_such_job)
+        };
+        let matching = |matches: &dyn Fn(&str) -> bool| {
+            let mut found = self.jobs.iter().filter(|job| matches(&job.command));
+            match (found.next(), found.next()) {
+                (Some(job), None) => Ok(job.id),
+                (Some(_), Some(_)) => Err(format!("{spec}: ambiguous job spec")),
+                (None, _) => Err(no_such_job()),
+            }
+        };
+
+        match text {
+            "" | "+" | "%" => recent(0),
+            "-" => recent(1),
+            _ if text.bytes().all(|b| b.is_ascii_digit()) => {
+                let id = text.parse::<usize>().map_err(|_| no_such_job())?;
+                match self.jobs.iter().any(|job| job.id == id) {
+                    true => Ok(id),
+                    false => Err(no_such_job()),
+                }
+            }
+            _ => match text.strip_prefix('?') {
+                Some(part) => matching(&|command| command.contains(part)),
+                None => matching(&|command| command.starts_with(text)),
+            },
+        }
+    }
+
+    /// Whether the shell should hold off exiting, the first time it's about
+    /// to with jobs stopped.
+    pub fn warn_stopped(&mut self) -> bool {
+        let stopped = self.jobs.iter().any(|job| job.state == JobState::Stopped);
+        if !stopped || self.warned_stopped {
+            return false;
+        }
+
+        eprintln!("There are stopped jobs.");
+        self.warned_stopped = true;
+        true
+    }
+
+    /// Send SIGHUP to every job the shell is leaving behind, except those
+    /// marked with `disown -h`. Stopped jobs are continued to receive it.
+    pub fn hangup(&mut self) {
+        // The process group of a finished job may belong to another by now
+        self.update();
+        let left = self
+            .jobs
+            .iter()
+            .filter(|job| !job.nohup && !job.state.is_finished());
+
+        for job in left {
+            let _ = job.signal(SIGHUP);
+            if job.state == JobState::Stopped {
+                let _ = job.signal(SIGCONT);
+            }
+        }
+    }
+
+    /// Reap whatever processes have exited or stopped without blocking.
+    pub fn update(&mut self) {
+        for job in self.jobs.iter_mut() {
+            job.wait(false);
+        }
+    }
+
+    /// Remove the jobs that have finished, along with the marker each had, so
+    /// they are reported only once.
+    pub fn remove_done(&mut self) -> Vec<(char, BackgroundJob)> {
+        let done: Vec<(char, usize)> = self
+            .jobs
+            .iter()
+            .filter(|job| job.state.is_finished())
+            .map(|job| (self.marker(job.id), job.id))
+            .collect();
+
+        done.into_iter()
+            .filter_map(|(marker, id)| Some((marker, self.remove(id)?)))
+            .collect()
+    }
+}
+
+impl BackgroundJob {
+    pub fn new(pids: Vec<i32>, pgid: i32, command: String) -> BackgroundJob {
+        BackgroundJob {
+            id: 0,
+            pgid,
+            statuses: vec![None; pids.len()],
+            pids,
+            command,
+            state: JobState::Running,
+            nohup: false,
+        }
+    }
+
+    /// Send `sig` to every process of the job.
+    pub fn signal(&self, sig: c_int) -> std::io::Result<()> {
+        let result = match self.pgid {
+            0 => self.pids.iter().map(|pid| unsafe { kill(*pid, sig) }).min(),
+            pgid => Some(unsafe { kill(-pgid, sig) }),
+        };
+
+        match result {
+            Some(-1) => Err(std::io::Error::last_os_error()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Continue a stopped job.
+    pub fn resume(&mut self) {
+        self.state = JobState::Running;
+        let _ = self.signal(SIGCONT);
+    }
+
+    /// Collect status changes of the processes, blocking until every one of
+    /// them has exited or stopped when `block` is set.
+    pub(super) fn wait(&mut self, block: bool) {
+        // Only polling picks up jobs continued from outside, a blocking wait
+        // would return on the continue that resumed it
+        let flags = match block {
+            true => WUNTRACED,
+            false => WUNTRACED | WCONTINUED | WNOHANG,
+        };
+
+        let mut stopped = false;
+        let mut continued = false;
+        for (pid, status) in self.pids.iter().zip(self.statuses.iter_mut()) {
+            if status.is_some() {
+                continue;
+            }
+
+            let mut raw = 0;
+            if unsafe { waitpid(*pid, &mut raw, flags) } != *pid {
+                continue;
+            }
+            if WIFSTOPPED(raw) {
+                stopped = true;
+            } else if WIFCONTINUED(raw) {
+                continued = true;
+            } else {
+                *status = Some(raw);
+            }
+        }
+
+        // The last process decides once every one has been reaped
+        let last = self.statuses.last().copied().flatten();
+        if let Some(raw) = last
+            && self.statuses.iter().all(Option::is_some)
+        {
+            self.state = match WIFSIGNALED(raw) {
+                true => JobState::Killed {
+                    signal: WTERMSIG(raw),
+                    core_dumped: WCOREDUMP(raw),
+                },
+                false => JobState::Done(WEXITSTATUS(raw)),
+            };
+        } else if stopped {
+            self.state = JobState::Stopped;
+        } else if continued {
+            self.state = JobState::Running;
+        }
+    }
+
+    /// The line `jobs` shows for this job, e.g.
+    /// `[1]+  Running                 sleep 10 &`. With `pid`, the process id
+    /// replaces the second space.
+    pub fn describe(&self, marker: char, pid: bool) -> String {
+        let pid = match pid {
+            true => format!("{} ", self.pids[0]),
+            false => " ".to_string(),
+        };
+        let state = self.state.to_string();
+        let suffix = if self.state == JobState::Running {
+            " &"
+        } else {
+            ""
+        };
+
+        format!(
+            "[{}]{marker} {pid}{state:<24}{}{suffix}",
+            self.id, self.command
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn add(table: &mut JobTable, pids: Vec<i32>, command: &str) -> usize {
+        table.add(BackgroundJob::new(pids, 0, command.to_string()))
+    }
+
+    #[test]
+    fn test_markers() {
+        let mut table = JobTable::default();
+        assert_eq!(1, add(&mut table, vec![100], "a"));
+        assert_eq!(2, add(&mut table, vec![200, 201], "b | c"));
+        assert_eq!(3, add(&mut table, vec![300], "d"));
+
+        assert_eq!(['+', '-', ' '], [3, 2, 1].map(|id| table.marker(id)));
+
+        table.jobs[2].state = JobState::Done(0);
+        table.jobs[0].state = JobState::Done(1);
+        let done = table.remove_done();
+        let done: Vec<_> = done
+            .iter()
+            .map(|(m, job)| job.describe(*m, false))
+            .collect();
+        assert_eq!(
+            vec![
+                "[1]   Exit 1                  a",
+                "[3]+  Done                    d"
+            ],
+            done
+        );
+
+        // The highest number is free again
+        assert_eq!(3, add(&mut table, vec![400], "e"));
+        assert_eq!(
+            "[2]- 200 Running                 b | c &",
+            table.jobs[0].describe(table.marker(2), true)
+        );
+
+        // A job put back keeps its number and becomes the current job
+        let job = table.remove(2).unwrap();
+        assert_eq!(2, table.add(job));
+        assert_eq!(['+', '-'], [2, 3].map(|id| table.marker(id)));
+    }
+
+    #[test]
+    fn test_find() {
+        let mut table = JobTable::default();
+        assert_eq!(Err("%+: no such job".to_string()), table.find("%+"));
+
+        add(&mut table, vec![100], "sleep 10");
+        add(&mut table, vec![200], "vim notes");
+        add(&mut table, vec![300], "sleep 20");
+
+        assert_eq!(Ok(3), table.find("%%"));
+        assert_eq!(Ok(2), table.find("%-"));
+        assert_eq!(Ok(1), table.find("%1"));
+        assert_eq!(Ok(1), table.find("1"));
+        assert_eq!(Ok(2), table.find("%vim"));
+        assert_eq!(Ok(3), table.find("%?20"));
+        assert_eq!(
+            Err("%sleep: ambiguous job spec".to_string()),
+            table.find("%sleep")
+        );
+        assert_eq!(Err("%4: no such job".to_string()), table.find("%4"));
+    }
+}
@@ -1,10 +1,34 @@
+use crate::expand::{ParamExpr, Word, WordPart, expand_braces, is_special_param};
+use std::fmt;
+use std::str::Chars;
+
 #[derive(Debug, PartialEq)]
 pub enum Token {
     Literal(String),
-    // Complex(String),
+    Complex(Word),
+    // `(( expr ))` command
+    Arith(Word),
     Pipe,
     Write(i32),
     Append(i32),
+    Read(i32),
+    // `N>&M` / `N<&M`, the word that follows is the source fd or `-`
+    DupWrite(i32),
+    DupRead(i32),
+    WriteAll,
+    AppendAll,
+    // `N<<delim` / `N<<-delim` along with the body read from the lines that follow
+    HereDoc { fd: i32, body: Word },
+    // `N<<<word`
+    HereString(i32),
+    Semi,
+    // `&` after a command, run it in the background
+    Amp,
+    And,
+    Or,
+    Newline,
+    LParen,
+    RParen,
 }
 
 pub enum Quote {
@@ -13,112 +37,742 @@ pub enum Quote {
     Double,
 }
 
+/// Input the lexer can't make tokens of, `offset` is the byte offset in the
+/// input where the problem starts.
+#[derive(Debug, PartialEq)]
+pub struct LexError {
+    pub offset: usize,
+    pub kind: LexErrorKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LexErrorKind {
+    // A quote, `$(`, `${`, `((` or backquote left open, holding the character
+    // that would close it
+    UnterminatedQuote(char),
+    // A backslash with nothing after it
+    DanglingEscape,
+    // A redirect operator at the end of a line
+    MissingRedirectTarget,
+}
+
+impl fmt::Display for LexErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LexErrorKind::UnterminatedQuote(close) => {
+                write!(f, "unexpected EOF while looking for matching `{close}'")
+            }
+            LexErrorKind::DanglingEscape => write!(f, "unexpected EOF after `\\'"),
+            LexErrorKind::MissingRedirectTarget => write!(f, "missing redirect target"),
+        }
+    }
+}
+
+impl fmt::Display for LexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.kind)
+    }
+}
+
+impl std::error::Error for LexError {}
+
+impl LexError {
+    fn new(offset: usize, kind: LexErrorKind) -> LexError {
+        LexError { offset, kind }
+    }
+
+    /// The line of `input` holding the error with a caret under the offending
+    /// character, followed by the message.
+    pub fn annotate(&self, input: &str) -> String {
+        let offset = self.offset.min(input.len());
+        let start = input[..offset].rfind('\n').map_or(0, |i| i + 1);
+        let end = input[offset..]
+            .find('\n')
+            .map_or(input.len(), |i| offset + i);
+
+        // Tabs are kept so the caret lines up however they are displayed
+        let padding: String = input[start..offset]
+            .chars()
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+
+        format!("{}\n{padding}^ {}", &input[start..end], self.kind)
+    }
+}
+
 pub struct Lexer {
     buffer: String,
+    // Set when the input ended before every here-document was terminated
+    heredoc_pending: bool,
+}
+
+// The characters being tokenized, like `Peekable<Chars>` but keeping track of
+// the byte offset reached
+#[derive(Clone)]
+struct Input<'a> {
+    chars: Chars<'a>,
+    peeked: Option<char>,
+    len: usize,
+}
+
+impl<'a> Input<'a> {
+    fn new(text: &'a str) -> Self {
+        let mut chars = text.chars();
+        let peeked = chars.next();
+
+        Input {
+            chars,
+            peeked,
+            len: text.len(),
+        }
+    }
+
+    // Byte offset of the next character
+    fn offset(&self) -> usize {
+        self.len - self.chars.as_str().len() - self.peeked.map_or(0, char::len_utf8)
+    }
+
+    fn peek(&self) -> Option<&char> {
+        self.peeked.as_ref()
+    }
+
+    fn next_if(&mut self, func: impl FnOnce(&char) -> bool) -> Option<char> {
+        match self.peeked.as_ref().is_some_and(func) {
+            true => self.next(),
+            false => None,
+        }
+    }
+
+    fn next_if_eq(&mut self, expected: &char) -> Option<char> {
+        self.next_if(|c| c == expected)
+    }
+}
+
+impl Iterator for Input<'_> {
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        std::mem::replace(&mut self.peeked, self.chars.next())
+    }
+}
+
+// A here-document whose body starts after the current line
+struct PendingHereDoc {
+    // Index of its `Token::HereDoc` to fill in
+    index: usize,
+    delimiter: String,
+    quoted: bool,
+    strip_tabs: bool,
 }
 
 impl Lexer {
     pub fn new() -> Lexer {
         Lexer {
             buffer: String::new(),
+            heredoc_pending: false,
         }
     }
 
+    /// Whether `input` still needs lines to complete a here-document body.
+    pub fn needs_heredoc_body(input: &str) -> bool {
+        let mut lex = Lexer::new();
+        lex.push(input);
+
+        lex.tokenize().is_ok() && lex.heredoc_pending
+    }
+
     pub fn push(&mut self, input: &str) {
         self.buffer.push_str(input);
     }
 
-    pub fn tokenize(&mut self) -> Vec<Token> {
-        let mut tk = String::new();
+    pub fn tokenize(&mut self) -> Result<Vec<Token>, LexError> {
+        let mut tk = Word::new();
         let mut tokens = Vec::new();
         let mut quote = Quote::None;
-        let mut chars = self.buffer.chars().peekable();
+        let mut quote_start = 0;
+        let mut heredocs = Vec::new();
+        let buffer = std::mem::take(&mut self.buffer);
+        let mut chars = Input::new(&buffer);
+        self.heredoc_pending = false;
 
         while let Some(ch) = chars.next() {
-            match (ch, &quote) {
-                ('\'', Quote::None) => quote = Quote::Single,
-                ('\'', Quote::Single) => quote = Quote::None,
-
-                ('\"', Quote::None) => quote = Quote::Double,
-                ('\"', Quote::Double) => quote = Quote::None,
+            let start = chars.offset() - ch.len_utf8();
+            let token_count = tokens.len();
 
-                ('|', Quote::None) => tokens.push(Token::Pipe),
-
-                ('\\', Quote::None) => {
-                    let Some(c) = chars.peek() else { todo!() };
+            match (ch, &quote) {
+                ('|', Quote::None) => {
+                    Self::flush_token(&mut tk, &mut tokens);
+                    match chars.next_if_eq(&'|') {
+                        Some(_) => tokens.push(Token::Or),
+                        None => tokens.push(Token::Pipe),
+                    }
+                }
 
-                    tk.push(*c);
+                ('&', Quote::None) if chars.peek() == Some(&'&') => {
                     chars.next();
+                    Self::flush_token(&mut tk, &mut tokens);
+                    tokens.push(Token::And);
+                }
+
+                (';', Quote::None) => {
+                    Self::flush_token(&mut tk, &mut tokens);
+                    tokens.push(Token::Semi);
                 }
-                ('\\', Quote::Double) => {
-                    let Some(next_char) = chars.peek() else {
-                        todo!()
-                    };
 
-                    match next_char {
-                        '"' | '\\' | '$' | '`' | '\n' => {
-                            tk.push(*next_char);
+                ('>', Quote::None) => {
+                    let fd = Self::take_io_number(&mut tk).unwrap_or(1);
+                    Self::flush_token(&mut tk, &mut tokens);
+
+                    match chars.peek() {
+                        Some('>') => {
                             chars.next();
+                            tokens.push(Token::Append(fd))
                         }
-                        _ => {
-                            tk.push(ch);
+                        Some('&') => {
+                            chars.next();
+                            tokens.push(Token::DupWrite(fd))
                         }
+                        _ => tokens.push(Token::Write(fd)),
                     }
                 }
 
-                // TODO: Handle & for all fd redirect/append
-                ('>', Quote::None) => {
-                    let Some(next_char) = chars.peek() else {
-                        todo!()
+                // `&>` and `&>>` send both stdout and stderr to a file
+                ('&', Quote::None) if chars.peek() == Some(&'>') => {
+                    chars.next();
+                    Self::flush_token(&mut tk, &mut tokens);
+
+                    if chars.peek() == Some(&'>') {
+                        chars.next();
+                        tokens.push(Token::AppendAll);
+                    } else {
+                        tokens.push(Token::WriteAll);
+                    }
+                }
+
+                ('&', Quote::None) => {
+                    Self::flush_token(&mut tk, &mut tokens);
+                    tokens.push(Token::Amp);
+                }
+
+                ('(', Quote::None) if tk.is_empty() && chars.peek() == Some(&'(') => {
+                    chars.next();
+                    let Some(expr) = Self::take_arith(&mut chars) else {
+                        return Err(LexError::new(start, LexErrorKind::UnterminatedQuote(')')));
                     };
-                    let fd = Self::take_io_number(&mut tk).unwrap_or(1);
-                    Self::flush_token(&mut tk, &mut tokens, Token::Literal);
+                    tokens.push(Token::Arith(Self::lex_arith(&expr)));
+                }
+
+                ('(', Quote::None) => {
+                    Self::flush_token(&mut tk, &mut tokens);
+                    tokens.push(Token::LParen);
+                }
+
+                (')', Quote::None) => {
+                    Self::flush_token(&mut tk, &mut tokens);
+                    tokens.push(Token::RParen);
+                }
 
-                    match next_char {
-                        '>' => {
+                ('<', Quote::None) => {
+                    let fd = Self::take_io_number(&mut tk).unwrap_or(0);
+                    Self::flush_token(&mut tk, &mut tokens);
+
+                    match chars.peek().copied() {
+                        Some('&') => {
                             chars.next();
-                            tokens.push(Token::Append(fd))
+                            tokens.push(Token::DupRead(fd));
                         }
-                        _ => tokens.push(Token::Write(fd)),
+                        Some('<') if chars.clone().nth(1) == Some('<') => {
+                            chars.nth(1);
+                            tokens.push(Token::HereString(fd));
+                        }
+                        Some('<') => {
+                            chars.next();
+                            let strip_tabs = chars.next_if_eq(&'-').is_some();
+                            let (delimiter, quoted) = Self::take_delimiter(&mut chars);
+                            if delimiter.is_empty() && !quoted {
+                                return Err(LexError::new(
+                                    start,
+                                    LexErrorKind::MissingRedirectTarget,
+                                ));
+                            }
+
+                            tokens.push(Token::HereDoc {
+                                fd,
+                                body: Word::new(),
+                            });
+                            heredocs.push(PendingHereDoc {
+                                index: tokens.len() - 1,
+                                delimiter,
+                                quoted,
+                                strip_tabs,
+                            });
+                        }
+                        _ => tokens.push(Token::Read(fd)),
                     }
                 }
 
+                // A comment runs to the end of the line, which still ends the command
+                ('#', Quote::None) if tk.is_empty() => {
+                    while chars.next_if(|c| *c != '\n').is_some() {}
+                }
+
+                // Here-document bodies start on the line after their operator
+                ('\n', Quote::None) => {
+                    Self::flush_token(&mut tk, &mut tokens);
+                    tokens.push(Token::Newline);
+                    Self::read_heredocs(&mut chars, &mut tokens, &mut heredocs);
+                }
+
                 (ch, Quote::None) if ch.is_ascii_whitespace() => {
-                    Self::flush_token(&mut tk, &mut tokens, Token::Literal);
+                    Self::flush_token(&mut tk, &mut tokens);
                 }
                 _ => {
-                    tk.push(ch);
+                    let was_quoted = !matches!(quote, Quote::None);
+                    Self::push_word_char(ch, &mut quote, &mut chars, &mut tk)
+                        .map_err(|kind| LexError::new(start, kind))?;
+                    if !was_quoted {
+                        quote_start = start;
+                    }
+                }
+            }
+
+            // A redirect operator needs a target before the end of the line
+            let is_redirect = tokens[token_count..].iter().any(|tk| {
+                matches!(
+                    tk,
+                    Token::Write(_)
+                        | Token::Append(_)
+                        | Token::Read(_)
+                        | Token::DupWrite(_)
+                        | Token::DupRead(_)
+                        | Token::WriteAll
+                        | Token::AppendAll
+                        | Token::HereString(_)
+                )
+            });
+            if is_redirect && Self::at_line_end(&chars) {
+                return Err(LexError::new(start, LexErrorKind::MissingRedirectTarget));
+            }
+        }
+
+        let close = match quote {
+            Quote::Single => Some('\''),
+            Quote::Double => Some('"'),
+            Quote::None => None,
+        };
+        if let Some(close) = close {
+            return Err(LexError::new(
+                quote_start,
+                LexErrorKind::UnterminatedQuote(close),
+            ));
+        }
+
+        Self::flush_token(&mut tk, &mut tokens);
+        self.heredoc_pending = !heredocs.is_empty();
+
+        Ok(tokens)
+    }
+
+    // Only blanks are left before the next newline or the end of input
+    fn at_line_end(chars: &Input) -> bool {
+        chars
+            .clone()
+            .take_while(|c| *c != '\n')
+            .all(|c| c == ' ' || c == '\t')
+    }
+
+    // Read a here-document delimiter, noting whether any of it was quoted
+    fn take_delimiter(chars: &mut Input) -> (String, bool) {
+        let mut delimiter = String::new();
+        let mut quoted = false;
+        let mut quote = Quote::None;
+
+        while chars.next_if(|c| *c == ' ' || *c == '\t').is_some() {}
+
+        while let Some(&ch) = chars.peek() {
+            match (ch, &quote) {
+                (ch, Quote::None) if ch.is_ascii_whitespace() || ";|&<>()".contains(ch) => break,
+                ('\'', Quote::None) => quote = Quote::Single,
+                ('"', Quote::None) => quote = Quote::Double,
+                ('\'', Quote::Single) | ('"', Quote::Double) => quote = Quote::None,
+                ('\\', Quote::None) => {
+                    chars.next();
+                    delimiter.extend(chars.peek());
+                }
+                (ch, _) => delimiter.push(ch),
+            }
+
+            quoted |= matches!(ch, '\'' | '"' | '\\');
+            chars.next();
+        }
+
+        (delimiter, quoted)
+    }
+
+    // Fill in the bodies of pending here-documents from the following lines,
+    // leaving them pending if the input runs out first
+    fn read_heredocs(chars: &mut Input, tokens: &mut [Token], heredocs: &mut Vec<PendingHereDoc>) {
+        while !heredocs.is_empty() {
+            let heredoc = &heredocs[0];
+            let mut body = String::new();
+            let mut terminated = false;
+
+            while chars.peek().is_some() {
+                let mut line: String = chars.by_ref().take_while(|c| *c != '\n').collect();
+                if heredoc.strip_tabs {
+                    line = line.trim_start_matches('\t').to_string();
+                }
+
+                if line == heredoc.delimiter {
+                    terminated = true;
+                    break;
+                }
+                body.push_str(&line);
+                body.push('\n');
+            }
+
+            if !terminated {
+                return;
+            }
+
+            let body = match heredoc.quoted {
+                true => Word::literal(&body),
+                false => Self::lex_heredoc(&body),
+            };
+            if let Token::HereDoc { body: slot, .. } = &mut tokens[heredoc.index] {
+                *slot = body;
+            }
+            heredocs.remove(0);
+        }
+    }
+
+    // An unquoted here-document body expands `$` and backquotes, a backslash
+    // only escapes `$`, `` ` ``, `\\` and newline
+    fn lex_heredoc(text: &str) -> Word {
+        let mut tk = Word::new();
+        let mut chars = Input::new(text);
+
+        tk.push_empty_quote();
+        while let Some(ch) = chars.next() {
+            match ch {
+                // An unterminated expansion just ends the body
+                '$' => {
+                    let _ = Self::take_dollar(&mut chars, &mut tk, true);
+                }
+                '`' => {
+                    let _ = Self::take_backquoted(&mut chars, &mut tk, true);
                 }
+                '\\' => match chars.peek() {
+                    Some('\n') => {
+                        chars.next();
+                    }
+                    Some(&c) if matches!(c, '$' | '`' | '\\') => {
+                        tk.push_char(c, true);
+                        chars.next();
+                    }
+                    _ => tk.push_char(ch, true),
+                },
+                _ => tk.push_char(ch, true),
             }
         }
 
-        Self::flush_token(&mut tk, &mut tokens, Token::Literal);
-        self.buffer.clear();
+        tk
+    }
+
+    /// Lex text into a single word without splitting on blanks or operators,
+    /// as used for the operands inside `${...}`. Anything left unterminated
+    /// just runs to the end of the text.
+    pub fn lex_word(text: &str) -> Word {
+        let mut tk = Word::new();
+        let mut quote = Quote::None;
+        let mut chars = Input::new(text);
+
+        while let Some(ch) = chars.next() {
+            let _ = Self::push_word_char(ch, &mut quote, &mut chars, &mut tk);
+        }
 
-        tokens
+        tk
     }
 
-    fn flush_token(tk: &mut String, tokens: &mut Vec<Token>, kind: fn(String) -> Token) {
-        if !tk.is_empty() {
-            tokens.push(kind(std::mem::take(tk)));
+    // Arithmetic text is expanded as if double quoted so results are never split
+    fn lex_arith(text: &str) -> Word {
+        let mut tk = Word::new();
+        let mut quote = Quote::Double;
+        let mut chars = Input::new(text);
+
+        tk.push_empty_quote();
+        while let Some(ch) = chars.next() {
+            let _ = Self::push_word_char(ch, &mut quote, &mut chars, &mut tk);
         }
+
+        tk
     }
 
-    fn take_io_number(tk: &mut String) -> Option<i32> {
-        if !tk.bytes().all(|b| b.is_ascii_digit()) {
+    // Handle quoting, escapes and expansions for a character that is part of a word
+    fn push_word_char(
+        ch: char,
+        quote: &mut Quote,
+        chars: &mut Input,
+        tk: &mut Word,
+    ) -> Result<(), LexErrorKind> {
+        match (ch, &*quote) {
+            ('\'', Quote::None) => {
+                *quote = Quote::Single;
+                tk.push_empty_quote();
+            }
+            ('\'', Quote::Single) => *quote = Quote::None,
+
+            ('\"', Quote::None) => {
+                *quote = Quote::Double;
+                tk.push_empty_quote();
+            }
+            ('\"', Quote::Double) => *quote = Quote::None,
+
+            ('$', Quote::None) => Self::take_dollar(chars, tk, false)?,
+            ('$', Quote::Double) => Self::take_dollar(chars, tk, true)?,
+
+            ('`', Quote::None) => Self::take_backquoted(chars, tk, false)?,
+            ('`', Quote::Double) => Self::take_backquoted(chars, tk, true)?,
+
+            ('\\', Quote::None) => match chars.next() {
+                // Line continuation
+                Some('\n') => {}
+                Some(c) => tk.push_char(c, true),
+                None => return Err(LexErrorKind::DanglingEscape),
+            },
+            ('\\', Quote::Double) => {
+                // The open quote is what gets reported
+                let Some(next_char) = chars.peek() else {
+                    tk.push_char(ch, true);
+                    return Ok(());
+                };
+
+                match next_char {
+                    '\n' => {
+                        chars.next();
+                    }
+                    '"' | '\\' | '$' | '`' => {
+                        tk.push_char(*next_char, true);
+                        chars.next();
+                    }
+                    _ => {
+                        tk.push_char(ch, true);
+                    }
+                }
+            }
+
+            (ch, Quote::None) => tk.push_char(ch, false),
+            (ch, _) => tk.push_char(ch, true),
+        }
+
+        Ok(())
+    }
+
+    fn flush_token(tk: &mut Word, tokens: &mut Vec<Token>) {
+        if tk.is_empty() {
+            return;
+        }
+
+        // Brace expansion comes first and may turn one word into many, except
+        // in an assignment, which the parser expands if it's an argument
+        // instead. Only unquoted text can be a reserved word, `'if'` is a
+        // complex word.
+        let words = match tk.split_assignment() {
+            Some(_) => vec![std::mem::take(tk)],
+            None => expand_braces(std::mem::take(tk)),
+        };
+        for word in words {
+            match word.as_plain() {
+                Some(text) if word.as_unquoted().is_some() => tokens.push(Token::Literal(text)),
+                _ => tokens.push(Token::Complex(word)),
+            }
+        }
+    }
+
+    fn take_io_number(tk: &mut Word) -> Option<i32> {
+        let digits = tk.as_unquoted()?;
+        if !digits.bytes().all(|b| b.is_ascii_digit()) {
             return None;
         }
 
-        let fd = tk.parse::<i32>().ok()?;
-        tk.clear();
+        let fd = digits.parse::<i32>().ok()?;
+        *tk = Word::new();
         Some(fd)
     }
+
+    // Read what follows an unescaped `$`, leaving it literal if no expansion applies
+    fn take_dollar(chars: &mut Input, tk: &mut Word, quoted: bool) -> Result<(), LexErrorKind> {
+        let unterminated = LexErrorKind::UnterminatedQuote;
+
+        let expr = match chars.peek() {
+            Some('(') => {
+                chars.next();
+                if chars.peek() == Some(&'(') {
+                    chars.next();
+                    let expr = Self::take_arith(chars).ok_or(unterminated(')'))?;
+                    let expr = Self::lex_arith(&expr);
+                    tk.push_part(WordPart::Arith { expr, quoted });
+                } else {
+                    let source = Self::take_parenthesized(chars).ok_or(unterminated(')'))?;
+                    tk.push_part(WordPart::Command { source, quoted });
+                }
+                return Ok(());
+            }
+            Some('{') => {
+                chars.next();
+                ParamExpr::parse(&Self::take_braced(chars).ok_or(unterminated('}'))?)
+            }
+            Some(&c) if c == '_' || c.is_ascii_alphabetic() => {
+                let mut name = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c != '_' && !c.is_ascii_alphanumeric() {
+                        break;
+                    }
+                    name.push(c);
+                    chars.next();
+                }
+                ParamExpr::plain(&name)
+            }
+            Some(&c) if is_special_param(c) => {
+                chars.next();
+                ParamExpr::plain(&c.to_string())
+            }
+            _ => {
+                tk.push_char('$', quoted);
+                return Ok(());
+            }
+        };
+
+        tk.push_part(WordPart::Param { expr, quoted });
+        Ok(())
+    }
+
+    // Take the body of `${...}` up to its matching brace, skipping over quotes,
+    // escapes and nested expansions
+    fn take_braced(chars: &mut Input) -> Option<String> {
+        let mut body = String::new();
+        let mut depth = 0;
+        let mut quote = Quote::None;
+
+        while let Some(ch) = chars.next() {
+            match (ch, &quote) {
+                ('}', Quote::None) if depth == 0 => return Some(body),
+                ('}', Quote::None) => depth -= 1,
+                ('$', Quote::None | Quote::Double) if chars.peek() == Some(&'{') => {
+                    depth += 1;
+                    body.push(ch);
+                    body.extend(chars.next());
+                    continue;
+                }
+                ('\\', Quote::None | Quote::Double) => {
+                    body.push(ch);
+                    body.extend(chars.next());
+                    continue;
+                }
+                ('\'', Quote::None) => quote = Quote::Single,
+                ('\'', Quote::Single) => quote = Quote::None,
+                ('"', Quote::None) => quote = Quote::Double,
+                ('"', Quote::Double) => quote = Quote::None,
+                _ => {}
+            }
+            body.push(ch);
+        }
+
+        None
+    }
+
+    // Take the body of `$(...)` up to its matching parenthesis
+    fn take_parenthesized(chars: &mut Input) -> Option<String> {
+        let mut body = String::new();
+        let mut depth = 0;
+        let mut quote = Quote::None;
+
+        while let Some(ch) = chars.next() {
+            match (ch, &quote) {
+                (')', Quote::None) if depth == 0 => return Some(body),
+                (')', Quote::None) => depth -= 1,
+                ('(', Quote::None) => depth += 1,
+                ('\\', Quote::None | Quote::Double) => {
+                    body.push(ch);
+                    body.extend(chars.next());
+                    continue;
+                }
+                ('\'', Quote::None) => quote = Quote::Single,
+                ('\'', Quote::Single) => quote = Quote::None,
+                ('"', Quote::None) => quote = Quote::Double,
+                ('"', Quote::Double) => quote = Quote::None,
+                _ => {}
+            }
+            body.push(ch);
+        }
+
+        None
+    }
+
+    // Take an arithmetic expression up to the `))` closing `$((` or `((`
+    fn take_arith(chars: &mut Input) -> Option<String> {
+        let mut body = String::new();
+        let mut depth = 0;
+
+        while let Some(ch) = chars.next() {
+            match ch {
+                ')' if depth == 0 && chars.peek() == Some(&')') => {
+                    chars.next();
+                    return Some(body);
+                }
+                ')' => depth -= 1,
+                '(' => depth += 1,
+                _ => {}
+            }
+            body.push(ch);
+        }
+
+        None
+    }
+
+    // Take a `` `...` `` command, where a backslash only escapes `$`, `` ` ``,
+    // `\` and, within double quotes, `"`
+    fn take_backquoted(chars: &mut Input, tk: &mut Word, quoted: bool) -> Result<(), LexErrorKind> {
+        let mut source = String::new();
+
+        while let Some(ch) = chars.next() {
+            match ch {
+                '`' => {
+                    tk.push_part(WordPart::Command { source, quoted });
+                    return Ok(());
+                }
+                '\\' => match chars.peek() {
+                    Some(&c) if matches!(c, '$' | '`' | '\\') || (quoted && c == '"') => {
+                        source.push(c);
+                        chars.next();
+                    }
+                    _ => source.push(ch),
+                },
+                _ => source.push(ch),
+            }
+        }
+
+        Err(LexErrorKind::UnterminatedQuote('`'))
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    // A word token, `(text, quoted)` for each of its parts in order
+    fn complex(parts: &[(&str, bool)]) -> Token {
+        let mut word = Word::new();
+        for (text, quoted) in parts {
+            text.chars().for_each(|ch| word.push_char(ch, *quoted));
+        }
+        Token::Complex(word)
+    }
+
+    fn quoted(text: &str) -> Token {
+        complex(&[(text, true)])
+    }
+
     #[test]
     fn test_white_space() {
         let mut lex = Lexer::new();
@@ -128,14 +782,14 @@ mod tests {
             Token::Literal("hello".to_string()),
             Token::Literal("world".to_string()),
         ];
-        assert_eq!(expect, lex.tokenize());
+        assert_eq!(expect, lex.tokenize().unwrap());
 
         lex.push("  hello     world    ");
         let expect = vec![
             Token::Literal("hello".to_string()),
             Token::Literal("world".to_string()),
         ];
-        assert_eq!(expect, lex.tokenize());
+        assert_eq!(expect, lex.tokenize().unwrap());
     }
 
     #[test]
@@ -143,15 +797,12 @@ mod tests {
         let mut lex = Lexer::new();
 
         lex.push("'hello' world");
-        let expect = vec![
-            Token::Literal("hello".to_string()),
-            Token::Literal("world".to_string()),
-        ];
-        assert_eq!(expect, lex.tokenize());
+        let expect = vec![quoted("hello"), Token::Literal("world".to_string())];
+        assert_eq!(expect, lex.tokenize().unwrap());
 
         lex.push("'hello''world'");
-        let expect = vec![Token::Literal("helloworld".to_string())];
-        assert_eq!(expect, lex.tokenize());
+        let expect = vec![quoted("helloworld")];
+        assert_eq!(expect, lex.tokenize().unwrap());
     }
 
     #[test]
@@ -159,12 +810,12 @@ mod tests {
         let mut lex = Lexer::new();
 
         lex.push("\"hello     world\"");
-        let expect = vec![Token::Literal("hello     world".to_string())];
-        assert_eq!(expect, lex.tokenize());
+        let expect = vec![quoted("hello     world")];
+        assert_eq!(expect, lex.tokenize().unwrap());
 
         lex.push("\"shell's test\"");
-        let expect = vec![Token::Literal("shell's test".to_string())];
-        assert_eq!(expect, lex.tokenize());
+        let expect = vec![quoted("shell's test")];
+        assert_eq!(expect, lex.tokenize().unwrap());
     }
 
     #[test]
@@ -172,19 +823,27 @@ mod tests {
         let mut lex = Lexer::new();
 
         lex.push("multiple\\ \\ \\ \\ spaces");
-        let expect = vec![Token::Literal("multiple    spaces".to_string())];
-        assert_eq!(expect, lex.tokenize());
+        let expect = vec![complex(&[
+            ("multiple", false),
+            ("    ", true),
+            ("spaces", false),
+        ])];
+        assert_eq!(expect, lex.tokenize().unwrap());
 
         lex.push("\\'\\\"literal quotes\\\"\\'");
         let expect = vec![
-            Token::Literal("\'\"literal".to_string()),
-            Token::Literal("quotes\"\'".to_string()),
+            complex(&[("\'\"", true), ("literal", false)]),
+            complex(&[("quotes", false), ("\"\'", true)]),
         ];
-        assert_eq!(expect, lex.tokenize());
+        assert_eq!(expect, lex.tokenize().unwrap());
 
         lex.push("ignore\\_backslash");
-        let expect = vec![Token::Literal("ignore_backslash".to_string())];
-        assert_eq!(expect, lex.tokenize());
+        let expect = vec![complex(&[
+            ("ignore", false),
+            ("_", true),
+            ("backslash", false),
+        ])];
+        assert_eq!(expect, lex.tokenize().unwrap());
     }
 
     #[test]
@@ -192,12 +851,12 @@ mod tests {
         let mut lex = Lexer::new();
 
         lex.push("'shell\\\\\\nscript'");
-        let expect = vec![Token::Literal("shell\\\\\\nscript".to_string())];
-        assert_eq!(expect, lex.tokenize());
+        let expect = vec![quoted("shell\\\\\\nscript")];
+        assert_eq!(expect, lex.tokenize().unwrap());
 
         lex.push("'example\\\"test'");
-        let expect = vec![Token::Literal("example\\\"test".to_string())];
-        assert_eq!(expect, lex.tokenize());
+        let expect = vec![quoted("example\\\"test")];
+        assert_eq!(expect, lex.tokenize().unwrap());
     }
 
     #[test]
@@ -207,20 +866,20 @@ mod tests {
         let mut lex = Lexer::new();
 
         lex.push("\"\\hello \\world\"");
-        let expect = vec![Token::Literal("\\hello \\world".to_string())];
-        assert_eq!(expect, lex.tokenize());
+        let expect = vec![quoted("\\hello \\world")];
+        assert_eq!(expect, lex.tokenize().unwrap());
 
         lex.push("\"A \\ escapes itself\"");
-        let expect = vec![Token::Literal("A \\ escapes itself".to_string())];
-        assert_eq!(expect, lex.tokenize());
+        let expect = vec![quoted("A \\ escapes itself")];
+        assert_eq!(expect, lex.tokenize().unwrap());
 
         lex.push("\"A \\\" inside double quotes\"");
-        let expect = vec![Token::Literal("A \" inside double quotes".to_string())];
-        assert_eq!(expect, lex.tokenize());
+        let expect = vec![quoted("A \" inside double quotes")];
+        assert_eq!(expect, lex.tokenize().unwrap());
 
         lex.push("\"\\$ is a Dollar sign\" ");
-        let expect = vec![Token::Literal("$ is a Dollar sign".to_string())];
-        assert_eq!(expect, lex.tokenize());
+        let expect = vec![quoted("$ is a Dollar sign")];
+        assert_eq!(expect, lex.tokenize().unwrap());
     }
 
     #[test]
@@ -229,19 +888,19 @@ mod tests {
 
         lex.push("> test.txt");
         let expect = vec![Token::Write(1), Token::Literal("test.txt".to_string())];
-        assert_eq!(expect, lex.tokenize());
+        assert_eq!(expect, lex.tokenize().unwrap());
 
         lex.push("2> test.txt");
         let expect = vec![Token::Write(2), Token::Literal("test.txt".to_string())];
-        assert_eq!(expect, lex.tokenize());
+        assert_eq!(expect, lex.tokenize().unwrap());
 
         lex.push(">> test.txt");
         let expect = vec![Token::Append(1), Token::Literal("test.txt".to_string())];
-        assert_eq!(expect, lex.tokenize());
+        assert_eq!(expect, lex.tokenize().unwrap());
 
         lex.push("2>> test.txt");
         let expect = vec![Token::Append(2), Token::Literal("test.txt".to_string())];
-        assert_eq!(expect, lex.tokenize());
+        assert_eq!(expect, lex.tokenize().unwrap());
 
         lex.push("just2>test.txt");
         let expect = vec![
@@ -249,6 +908,342 @@ mod tests {
             Token::Write(1),
             Token::Literal("test.txt".to_string()),
         ];
-        assert_eq!(expect, lex.tokenize());
+        assert_eq!(expect, lex.tokenize().unwrap());
+    }
+
+    #[test]
+    fn test_duplicate() {
+        let mut lex = Lexer::new();
+
+        lex.push("cmd > out 2>&1 <&3 4>&-");
+        let expect = vec![
+            Token::Literal("cmd".to_string()),
+            Token::Write(1),
+            Token::Literal("out".to_string()),
+            Token::DupWrite(2),
+            Token::Literal("1".to_string()),
+            Token::DupRead(0),
+            Token::Literal("3".to_string()),
+            Token::DupWrite(4),
+            Token::Literal("-".to_string()),
+        ];
+        assert_eq!(expect, lex.tokenize().unwrap());
+
+        lex.push("cmd &> all &>>log 'a&b'");
+        let expect = vec![
+            Token::Literal("cmd".to_string()),
+            Token::WriteAll,
+            Token::Literal("all".to_string()),
+            Token::AppendAll,
+            Token::Literal("log".to_string()),
+            quoted("a&b"),
+        ];
+        assert_eq!(expect, lex.tokenize().unwrap());
+    }
+
+    #[test]
+    fn test_heredoc() {
+        let mut lex = Lexer::new();
+
+        lex.push("cat <<EOF > out\nhello $USER\nEOF\necho done");
+        let mut body = Word::literal("hello ");
+        body.push_part(WordPart::Param {
+            expr: ParamExpr::plain("USER"),
+            quoted: true,
+        });
+        body.push_char('\n', true);
+        let tokens = lex.tokenize().unwrap();
+        assert_eq!(Token::Literal("cat".to_string()), tokens[0]);
+        assert_eq!(Token::HereDoc { fd: 0, body }, tokens[1]);
+        assert_eq!(Token::Write(1), tokens[2]);
+        assert_eq!(
+            vec![
+                Token::Newline,
+                Token::Literal("echo".to_string()),
+                Token::Literal("done".to_string()),
+            ],
+            tokens[4..]
+        );
+
+        lex.push("cat <<-'EOF'\n\t$HOME\n\tEOF");
+        let expect = vec![
+            Token::Literal("cat".to_string()),
+            Token::HereDoc {
+                fd: 0,
+                body: Word::literal("$HOME\n"),
+            },
+            Token::Newline,
+        ];
+        assert_eq!(expect, lex.tokenize().unwrap());
+    }
+
+    #[test]
+    fn test_herestring() {
+        let mut lex = Lexer::new();
+
+        lex.push("grep foo <<< \"$text\"");
+        let mut word = Word::new();
+        word.push_empty_quote();
+        word.push_part(param("text", true));
+        let expect = vec![
+            Token::Literal("grep".to_string()),
+            Token::Literal("foo".to_string()),
+            Token::HereString(0),
+            Token::Complex(word),
+        ];
+        assert_eq!(expect, lex.tokenize().unwrap());
+
+        lex.push("read 3<<<word");
+        let expect = vec![
+            Token::Literal("read".to_string()),
+            Token::HereString(3),
+            Token::Literal("word".to_string()),
+        ];
+        assert_eq!(expect, lex.tokenize().unwrap());
+        assert!(!Lexer::needs_heredoc_body("cat <<< EOF"));
+    }
+
+    #[test]
+    fn test_heredoc_pending() {
+        assert!(Lexer::needs_heredoc_body("cat <<EOF"));
+        assert!(Lexer::needs_heredoc_body("cat <<EOF\nline"));
+        assert!(Lexer::needs_heredoc_body("cat <<A <<B\nA\nline"));
+        assert!(!Lexer::needs_heredoc_body("cat <<A <<B\nA\nB"));
+        assert!(!Lexer::needs_heredoc_body("cat <<\"E F\"\nE F"));
+    }
+
+    #[test]
+    fn test_list_operators() {
+        let mut lex = Lexer::new();
+
+        lex.push("a&&b||c;d|e 'f;g'");
+        let expect = vec![
+            Token::Literal("a".to_string()),
+            Token::And,
+            Token::Literal("b".to_string()),
+            Token::Or,
+            Token::Literal("c".to_string()),
+            Token::Semi,
+            Token::Literal("d".to_string()),
+            Token::Pipe,
+            Token::Literal("e".to_string()),
+            quoted("f;g"),
+        ];
+        assert_eq!(expect, lex.tokenize().unwrap());
+
+        lex.push("a&b & ");
+        let expect = vec![
+            Token::Literal("a".to_string()),
+            Token::Amp,
+            Token::Literal("b".to_string()),
+            Token::Amp,
+        ];
+        assert_eq!(expect, lex.tokenize().unwrap());
+
+        lex.push("(cd src)>f");
+        let expect = vec![
+            Token::LParen,
+            Token::Literal("cd".to_string()),
+            Token::Literal("src".to_string()),
+            Token::RParen,
+            Token::Write(1),
+            Token::Literal("f".to_string()),
+        ];
+        assert_eq!(expect, lex.tokenize().unwrap());
+    }
+
+    #[test]
+    fn test_comment() {
+        let mut lex = Lexer::new();
+
+        // A quote inside a comment doesn't open anything
+        lex.push("echo a#b '#c' \"#d\" \\#e #f 'g\nls;# h");
+        let expect = vec![
+            Token::Literal("echo".to_string()),
+            Token::Literal("a#b".to_string()),
+            quoted("#c"),
+            quoted("#d"),
+            complex(&[("#", true), ("e", false)]),
+            Token::Newline,
+            Token::Literal("ls".to_string()),
+            Token::Semi,
+        ];
+        assert_eq!(expect, lex.tokenize().unwrap());
+
+        lex.push("# only a comment");
+        assert!(lex.tokenize().unwrap().is_empty());
+
+        lex.push("echo $# ${#x}");
+        let tokens = lex.tokenize().unwrap();
+        assert_eq!(3, tokens.len());
+    }
+
+    #[test]
+    fn test_errors() {
+        use LexErrorKind::*;
+
+        let error = |input: &str| {
+            let mut lex = Lexer::new();
+            lex.push(input);
+            lex.tokenize().err().map(|e| (e.offset, e.kind))
+        };
+
+        assert_eq!(Some((5, UnterminatedQuote('"'))), error("echo \"abc"));
+        assert_eq!(Some((8, UnterminatedQuote('\''))), error("echo \"'\"'abc"));
+        assert_eq!(Some((5, UnterminatedQuote(')'))), error("echo $(ls"));
+        assert_eq!(Some((5, UnterminatedQuote('}'))), error("echo ${x"));
+        assert_eq!(Some((5, UnterminatedQuote('`'))), error("echo `ls"));
+        assert_eq!(Some((0, UnterminatedQuote(')'))), error("(( 1 + 2"));
+        assert_eq!(Some((5, DanglingEscape)), error("echo \\"));
+        assert_eq!(Some((6, MissingRedirectTarget)), error("echo 2> "));
+        assert_eq!(Some((5, MissingRedirectTarget)), error("echo <<<\nls"));
+        assert_eq!(Some((4, MissingRedirectTarget)), error("cat <<"));
+        assert_eq!(None, error("echo > ;"));
+        assert_eq!(None, error("echo 'a\nb' \\\n"));
+    }
+
+    #[test]
+    fn test_annotate() {
+        let input = "echo ok\n\techo \"abc";
+        let mut lex = Lexer::new();
+        lex.push(input);
+
+        let error = lex.tokenize().unwrap_err();
+        assert_eq!(
+            "\techo \"abc\n\t     ^ unexpected EOF while looking for matching `\"'",
+            error.annotate(input)
+        );
+    }
+
+    #[test]
+    fn test_read() {
+        let mut lex = Lexer::new();
+
+        lex.push("sort < data.txt");
+        let expect = vec![
+            Token::Literal("sort".to_string()),
+            Token::Read(0),
+            Token::Literal("data.txt".to_string()),
+        ];
+        assert_eq!(expect, lex.tokenize().unwrap());
+
+        lex.push("cmd 3<in<out");
+        let expect = vec![
+            Token::Literal("cmd".to_string()),
+            Token::Read(3),
+            Token::Literal("in".to_string()),
+            Token::Read(0),
+            Token::Literal("out".to_string()),
+        ];
+        assert_eq!(expect, lex.tokenize().unwrap());
+    }
+
+    fn param(name: &str, quoted: bool) -> WordPart {
+        WordPart::Param {
+            expr: ParamExpr::plain(name),
+            quoted,
+        }
+    }
+
+    #[test]
+    fn test_parameter() {
+        let mut lex = Lexer::new();
+
+        lex.push("$HOME ${HOME}/bin");
+        let mut first = Word::new();
+        first.push_part(param("HOME", false));
+        let mut second = Word::new();
+        second.push_part(param("HOME", false));
+        second.push_char('/', false);
+        second.push_char('b', false);
+        second.push_char('i', false);
+        second.push_char('n', false);
+        let expect = vec![Token::Complex(first), Token::Complex(second)];
+        assert_eq!(expect, lex.tokenize().unwrap());
+
+        lex.push("\"$HOME\"");
+        let mut word = Word::new();
+        word.push_empty_quote();
+        word.push_part(param("HOME", true));
+        let expect = vec![Token::Complex(word)];
+        assert_eq!(expect, lex.tokenize().unwrap());
+
+        lex.push("'$HOME' \\$HOME");
+        let expect = vec![quoted("$HOME"), complex(&[("$", true), ("HOME", false)])];
+        assert_eq!(expect, lex.tokenize().unwrap());
+
+        lex.push("cost: $ 5");
+        let expect = vec![
+            Token::Literal("cost:".to_string()),
+            Token::Literal("$".to_string()),
+            Token::Literal("5".to_string()),
+        ];
+        assert_eq!(expect, lex.tokenize().unwrap());
+    }
+
+    #[test]
+    fn test_command_substitution() {
+        let mut lex = Lexer::new();
+
+        lex.push("echo $(echo \"a)\" $(b)) `c \\`d\\``");
+        let mut first = Word::new();
+        first.push_part(WordPart::Command {
+            source: "echo \"a)\" $(b)".to_string(),
+            quoted: false,
+        });
+        let mut second = Word::new();
+        second.push_part(WordPart::Command {
+            source: "c `d`".to_string(),
+            quoted: false,
+        });
+        let expect = vec![
+            Token::Literal("echo".to_string()),
+            Token::Complex(first),
+            Token::Complex(second),
+        ];
+        assert_eq!(expect, lex.tokenize().unwrap());
+
+        lex.push("\"$(pwd)\"");
+        let mut word = Word::new();
+        word.push_empty_quote();
+        word.push_part(WordPart::Command {
+            source: "pwd".to_string(),
+            quoted: true,
+        });
+        let expect = vec![Token::Complex(word)];
+        assert_eq!(expect, lex.tokenize().unwrap());
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let mut lex = Lexer::new();
+
+        lex.push("echo $(( (1 + 2) * x ))");
+        let mut word = Word::new();
+        word.push_part(WordPart::Arith {
+            expr: Lexer::lex_arith(" (1 + 2) * x "),
+            quoted: false,
+        });
+        let expect = vec![Token::Literal("echo".to_string()), Token::Complex(word)];
+        assert_eq!(expect, lex.tokenize().unwrap());
+
+        lex.push("(( i++ ))");
+        let expect = vec![Token::Arith(Word::literal(" i++ "))];
+        assert_eq!(expect, lex.tokenize().unwrap());
+    }
+
+    #[test]
+    fn test_brace_expansion() {
+        let mut lex = Lexer::new();
+
+        lex.push("mkdir -p src/{bin,lib} '{x,y}'");
+        let expect = vec![
+            Token::Literal("mkdir".to_string()),
+            Token::Literal("-p".to_string()),
+            Token::Literal("src/bin".to_string()),
+            Token::Literal("src/lib".to_string()),
+            quoted("{x,y}"),
+        ];
+        assert_eq!(expect, lex.tokenize().unwrap());
     }
 }
@@ -1,13 +1,17 @@
 mod commands;
 mod context;
+mod expand;
 mod job;
 mod lexer;
+mod parser;
 mod readline;
+mod signal;
+mod trap;
 
-use std::io;
+use std::io::{self, IsTerminal};
 use std::path::{Path, PathBuf};
 
-use job::Job;
+use job::List;
 use lexer::Lexer;
 
 use readline::ReadlineError;
@@ -20,6 +24,9 @@ use readline::make_readline_helper;
 
 fn main() -> io::Result<()> {
     let mut ctx = ShellContext::new();
+    if io::stdin().is_terminal() {
+        ctx.set_job_control(job::enable_job_control());
+    }
 
     let config = Config::builder()
         .completion_type(CompletionType::List)
@@ -34,24 +41,47 @@ fn main() -> io::Result<()> {
     loop {
         editor.set_helper(Some(make_readline_helper(&ctx))); // Not efficient since we need to copied some ctx value everytime
 
+        // Report background jobs that finished since the last prompt
+        ctx.jobs_mut().update();
+        for (marker, job) in ctx.jobs_mut().remove_done() {
+            eprintln!("{}", job.describe(marker, false));
+        }
+        trap::run_pending(&mut ctx);
+
         let readline = editor.readline("$ ");
         match readline {
             Ok(line) => {
-                // Preprocess line
+                // Preprocess line, the validator has already read any
+                // continuation lines into it
                 let line = line.trim();
 
                 // Add line to history which consume line this should move up
                 add_history(&mut ctx, &mut editor, line);
 
                 // Parsing line
-                lex.push(&line);
-                let tokens = lex.tokenize();
-
-                // Run job
-                let job = Job::new(tokens);
-                job.run(&mut ctx);
+                lex.push(line);
+                let tokens = match lex.tokenize() {
+                    Ok(tokens) => tokens,
+                    Err(e) => {
+                        eprintln!("{}", e.annotate(line));
+                        ctx.set_status(2);
+                        continue;
+                    }
+                };
+
+                // Run every pipeline on the line
+                match parser::parse(tokens) {
+                    Ok(list) => {
+                        List::new(&list).run(&mut ctx);
+                    }
+                    Err(e) => {
+                        eprintln!("{e}");
+                        ctx.set_status(2);
+                    }
+                }
             }
             Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) if ctx.jobs_mut().warn_stopped() => continue,
             Err(ReadlineError::Eof) => break,
             Err(e) => {
                 eprintln!("{e:?}");
@@ -61,7 +91,7 @@ fn main() -> io::Result<()> {
     }
 
     ctx.shutdown();
-    Ok(())
+    std::process::exit(ctx.status());
 }
 
 // TODO move these utils out
@@ -72,10 +102,8 @@ where
     I: History,
 {
     // Keep history in sync on both rustyline and builtin
-    if let Ok(result) = editor.add_history_entry(line) {
-        if result {
-            ctx.push_history(line);
-        }
+    if let Ok(true) = editor.add_history_entry(line) {
+        ctx.push_history(line);
     }
 }
 
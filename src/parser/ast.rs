@@ -0,0 +1,235 @@
+use std::fmt;
+
+use crate::expand::Word;
+
+/// And-or lists separated by `;`, `&` or newlines.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct List {
+    pub items: Vec<AndOr>,
+}
+
+/// Pipelines joined by `&&` and `||`, run in the background when followed by
+/// `&`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AndOr {
+    pub pipelines: Vec<(Connector, Pipeline)>,
+    pub background: bool,
+}
+
+/// How a pipeline is joined to the one before it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Connector {
+    Always,
+    And,
+    Or,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pipeline {
+    pub commands: Vec<Command>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Simple(SimpleCommand),
+    Compound(CompoundCommand, Vec<Redirect>),
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SimpleCommand {
+    pub assignments: Vec<Assignment>,
+    pub words: Vec<Word>,
+    pub redirects: Vec<Redirect>,
+}
+
+/// `NAME=value`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Assignment {
+    pub name: String,
+    pub value: Word,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CompoundCommand {
+    // `{ list; }`
+    Brace(List),
+    // `( list )`
+    Subshell(List),
+    // `if cond; then body; elif cond; then body; else body; fi`
+    If {
+        branches: Vec<(List, List)>,
+        otherwise: Option<List>,
+    },
+    // `while cond; do body; done`, or `until` which loops while it fails
+    While {
+        condition: List,
+        body: List,
+        until: bool,
+    },
+    // `for name in words; do body; done`, without `in` it loops over "$@"
+    For {
+        name: String,
+        words: Option<Vec<Word>>,
+        body: List,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Redirect {
+    Write { fd: i32, target: Word },
+    Append { fd: i32, target: Word },
+    Read { fd: i32, target: Word },
+    // `N>&M` / `N<&M`
+    Duplicate { fd: i32, source: Word },
+    // `N>&-` / `N<&-`
+    Close { fd: i32 },
+    // `&>file` / `&>>file`
+    WriteAll { target: Word },
+    AppendAll { target: Word },
+    // `N<<delim`, the body is expanded unless the delimiter was quoted
+    HereDoc { fd: i32, body: Word },
+    // `N<<<word`, fed with a trailing newline
+    HereString { fd: i32, word: Word },
+}
+
+// The AST printed back as shell source, used where a command has to be shown
+// to the user, e.g. in the job table
+
+impl fmt::Display for List {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, item) in self.items.iter().enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            match (item.background, i + 1 < self.items.len()) {
+                (true, _) => write!(f, "{item} &")?,
+                (false, true) => write!(f, "{item};")?,
+                (false, false) => write!(f, "{item}")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for AndOr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (connector, pipeline) in self.pipelines.iter() {
+            match connector {
+                Connector::Always => {}
+                Connector::And => write!(f, " && ")?,
+                Connector::Or => write!(f, " || ")?,
+            }
+            write!(f, "{pipeline}")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Pipeline {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, command) in self.commands.iter().enumerate() {
+            if i > 0 {
+                write!(f, " | ")?;
+            }
+            write!(f, "{command}")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Command::Simple(command) => write!(f, "{command}"),
+            Command::Compound(command, redirects) => {
+                write!(f, "{command}")?;
+                redirects.iter().try_for_each(|r| write!(f, " {r}"))
+            }
+        }
+    }
+}
+
+impl fmt::Display for SimpleCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let assignments = self.assignments.iter().map(|a| a as &dyn fmt::Display);
+        let words = self.words.iter().map(|w| w as &dyn fmt::Display);
+        let redirects = self.redirects.iter().map(|r| r as &dyn fmt::Display);
+
+        for (i, item) in assignments.chain(words).chain(redirects).enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{item}")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Assignment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}={}", self.name, self.value)
+    }
+}
+
+impl fmt::Display for CompoundCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CompoundCommand::Brace(list) => write!(f, "{{ {list}; }}"),
+            CompoundCommand::Subshell(list) => write!(f, "({list})"),
+            CompoundCommand::If {
+                branches,
+                otherwise,
+            } => {
+                for (i, (condition, body)) in branches.iter().enumerate() {
+                    let keyword = if i == 0 { "if" } else { "elif" };
+                    write!(f, "{keyword} {condition}; then {body}; ")?;
+                }
+                if let Some(body) = otherwise {
+                    write!(f, "else {body}; ")?;
+                }
+                write!(f, "fi")
+            }
+            CompoundCommand::While {
+                condition,
+                body,
+                until,
+            } => {
+                let keyword = if *until { "until" } else { "while" };
+                write!(f, "{keyword} {condition}; do {body}; done")
+            }
+            CompoundCommand::For { name, words, body } => {
+                write!(f, "for {name}")?;
+                if let Some(words) = words {
+                    write!(f, " in")?;
+                    words.iter().try_for_each(|w| write!(f, " {w}"))?;
+                }
+                write!(f, "; do {body}; done")
+            }
+        }
+    }
+}
+
+impl fmt::Display for Redirect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // The default fd of an operator is left out
+        let fd = |fd: i32, default: i32| match fd == default {
+            true => String::new(),
+            false => fd.to_string(),
+        };
+
+        match self {
+            Redirect::Write { fd: n, target } => write!(f, "{}>{target}", fd(*n, 1)),
+            Redirect::Append { fd: n, target } => write!(f, "{}>>{target}", fd(*n, 1)),
+            Redirect::Read { fd: n, target } => write!(f, "{}<{target}", fd(*n, 0)),
+            Redirect::Duplicate { fd: 0, source } => write!(f, "<&{source}"),
+            Redirect::Duplicate { fd: n, source } => write!(f, "{}>&{source}", fd(*n, 1)),
+            Redirect::Close { fd: 0 } => write!(f, "<&-"),
+            Redirect::Close { fd: n } => write!(f, "{}>&-", fd(*n, 1)),
+            Redirect::WriteAll { target } => write!(f, "&>{target}"),
+            Redirect::AppendAll { target } => write!(f, "&>>{target}"),
+            // The body isn't part of the line the command was written on
+            Redirect::HereDoc { fd: n, .. } => write!(f, "{}<<EOF", fd(*n, 0)),
+            Redirect::HereString { fd: n, word } => write!(f, "{}<<<{word}", fd(*n, 0)),
+        }
+    }
+}
@@ -0,0 +1,618 @@
+use std::fmt;
+use std::iter::Peekable;
+use std::vec::IntoIter;
+
+use crate::expand::{Word, expand_braces, is_name};
+use crate::lexer::Token;
+
+pub mod ast;
+
+use ast::{
+    AndOr, Assignment, Command, CompoundCommand, Connector, List, Pipeline, Redirect, SimpleCommand,
+};
+
+#[derive(Debug, PartialEq)]
+pub enum SyntaxError {
+    UnexpectedToken(String),
+    // The input ended inside a construct, more lines could complete it
+    UnexpectedEof,
+}
+
+impl fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SyntaxError::UnexpectedToken(token) => {
+                write!(f, "syntax error near unexpected token `{token}'")
+            }
+            SyntaxError::UnexpectedEof => write!(f, "syntax error: unexpected end of file"),
+        }
+    }
+}
+
+impl std::error::Error for SyntaxError {}
+
+// Words that only mean something where a command could start
+const RESERVED: [&str; 12] = [
+    "if", "then", "elif", "else", "fi", "while", "until", "do", "done", "for", "{", "}",
+];
+
+/// Parse a whole input into the list of and-or lists it holds.
+pub fn parse(tokens: Vec<Token>) -> Result<List, SyntaxError> {
+    let mut parser = Parser {
+        tokens: tokens.into_iter().peekable(),
+    };
+
+    let list = parser.list(&[])?;
+    match parser.tokens.next() {
+        None => Ok(list),
+        Some(tk) => Err(Parser::unexpected(Some(&tk))),
+    }
+}
+
+struct Parser {
+    tokens: Peekable<IntoIter<Token>>,
+}
+
+impl Parser {
+    // And-or lists up to the end of input or one of the reserved words in
+    // `until`, which is left for the caller
+    fn list(&mut self, until: &[&str]) -> Result<List, SyntaxError> {
+        let mut list = List::default();
+
+        loop {
+            self.skip_newlines();
+            match self.tokens.peek() {
+                None | Some(Token::RParen) => break,
+                Some(tk) if Self::reserved(tk).is_some_and(|w| until.contains(&w)) => break,
+                _ => {}
+            }
+
+            let mut and_or = self.and_or()?;
+            match self.tokens.peek() {
+                Some(Token::Semi | Token::Newline) => {
+                    self.tokens.next();
+                }
+                Some(Token::Amp) => {
+                    self.tokens.next();
+                    and_or.background = true;
+                }
+                _ => {
+                    list.items.push(and_or);
+                    break;
+                }
+            }
+            list.items.push(and_or);
+        }
+
+        Ok(list)
+    }
+
+    fn and_or(&mut self) -> Result<AndOr, SyntaxError> {
+        let mut pipelines = Vec::new();
+        let mut connector = Connector::Always;
+
+        loop {
+            pipelines.push((connector, self.pipeline()?));
+
+            connector = match self.tokens.peek() {
+                Some(Token::And) => Connector::And,
+                Some(Token::Or) => Connector::Or,
+                _ => break,
+            };
+            self.tokens.next();
+            self.skip_newlines();
+        }
+
+        Ok(AndOr {
+            pipelines,
+            background: false,
+        })
+    }
+
+    fn pipeline(&mut self) -> Result<Pipeline, SyntaxError> {
+        let mut commands = vec![self.command()?];
+
+        while self.tokens.next_if_eq(&Token::Pipe).is_some() {
+            self.skip_newlines();
+            commands.push(self.command()?);
+        }
+
+        Ok(Pipeline { commands })
+    }
+
+    fn command(&mut self) -> Result<Command, SyntaxError> {
+        let compound = match self.tokens.peek() {
+            None => return Err(SyntaxError::UnexpectedEof),
+            Some(Token::LParen) => {
+                self.tokens.next();
+                let list = self.non_empty(&[])?;
+                self.expect_token(Token::RParen)?;
+                CompoundCommand::Subshell(list)
+            }
+            Some(Token::Arith(_)) => {
+                let Some(Token::Arith(expr)) = self.tokens.next() else {
+                    unreachable!()
+                };
+                // `(( expr ))` is the same as `let "expr"`
+                let words = vec![Word::literal("let"), expr];
+                let mut command = SimpleCommand {
+                    words,
+                    ..Default::default()
+                };
+                while let Some(redirect) = self.redirect()? {
+                    command.redirects.push(redirect);
+                }
+                return Ok(Command::Simple(command));
+            }
+            Some(tk) => match Self::reserved(tk) {
+                Some("{") => {
+                    self.tokens.next();
+                    let list = self.non_empty(&["}"])?;
+                    self.expect_word("}")?;
+                    CompoundCommand::Brace(list)
+                }
+                Some("if") => self.if_clause()?,
+                Some(keyword @ ("while" | "until")) => {
+                    let until = keyword == "until";
+                    self.tokens.next();
+                    let condition = self.non_empty(&["do"])?;
+                    let body = self.do_group()?;
+                    CompoundCommand::While {
+                        condition,
+                        body,
+                        until,
+                    }
+                }
+                Some("for") => self.for_clause()?,
+                Some(_) => return Err(Self::unexpected(Some(tk))),
+                None => return self.simple_command().map(Command::Simple),
+            },
+        };
+
+        let mut redirects = Vec::new();
+        while let Some(redirect) = self.redirect()? {
+            redirects.push(redirect);
+        }
+
+        Ok(Command::Compound(compound, redirects))
+    }
+
+    fn if_clause(&mut self) -> Result<CompoundCommand, SyntaxError> {
+        let mut branches = Vec::new();
+        let mut otherwise = None;
+
+        // Consumes `if` first, then each `elif`
+        loop {
+            self.tokens.next();
+            let condition = self.non_empty(&["then"])?;
+            self.expect_word("then")?;
+            let body = self.non_empty(&["elif", "else", "fi"])?;
+            branches.push((condition, body));
+
+            match self.tokens.peek().and_then(Self::reserved) {
+                Some("elif") => continue,
+                Some("else") => {
+                    self.tokens.next();
+                    otherwise = Some(self.non_empty(&["fi"])?);
+                    self.expect_word("fi")?;
+                }
+                _ => self.expect_word("fi")?,
+            }
+            break;
+        }
+
+        Ok(CompoundCommand::If {
+            branches,
+            otherwise,
+        })
+    }
+
+    fn for_clause(&mut self) -> Result<CompoundCommand, SyntaxError> {
+        self.tokens.next();
+
+        let name = match self.tokens.next() {
+            Some(Token::Literal(name)) if is_name(&name) => name,
+            None => return Err(SyntaxError::UnexpectedEof),
+            tk => return Err(Self::unexpected(tk.as_ref())),
+        };
+
+        self.skip_newlines();
+        let mut words = None;
+        if self
+            .tokens
+            .next_if(|tk| *tk == Token::Literal("in".to_string()))
+            .is_some()
+        {
+            let mut list = Vec::new();
+            while let Some(word) = self.word() {
+                list.extend(Self::argument(word));
+            }
+            words = Some(list);
+        }
+
+        match self.tokens.peek() {
+            Some(Token::Semi | Token::Newline) => {
+                self.tokens.next();
+            }
+            _ if words.is_some() => return Err(Self::unexpected(self.tokens.peek())),
+            _ => {}
+        }
+        self.skip_newlines();
+
+        let body = self.do_group()?;
+        Ok(CompoundCommand::For { name, words, body })
+    }
+
+    // `do list done`
+    fn do_group(&mut self) -> Result<List, SyntaxError> {
+        self.expect_word("do")?;
+        let body = self.non_empty(&["done"])?;
+        self.expect_word("done")?;
+
+        Ok(body)
+    }
+
+    fn simple_command(&mut self) -> Result<SimpleCommand, SyntaxError> {
+        let mut command = SimpleCommand::default();
+
+        loop {
+            if let Some(redirect) = self.redirect()? {
+                command.redirects.push(redirect);
+                continue;
+            }
+
+            // Assignments only count before the command name
+            let Some(word) = self.word() else {
+                break;
+            };
+            match Self::assignment(&word) {
+                Some(assignment) if command.words.is_empty() => {
+                    command.assignments.push(assignment)
+                }
+                _ => command.words.extend(Self::argument(word)),
+            }
+        }
+
+        if command.assignments.is_empty()
+            && command.words.is_empty()
+            && command.redirects.is_empty()
+        {
+            return Err(Self::unexpected(self.tokens.peek()));
+        }
+
+        Ok(command)
+    }
+
+    fn assignment(word: &Word) -> Option<Assignment> {
+        let (name, value) = word.split_assignment()?;
+
+        Some(Assignment { name, value })
+    }
+
+    // A word that looks like an assignment but isn't one, e.g. `echo A={a,b}`,
+    // gets the brace expansion the lexer left out
+    fn argument(word: Word) -> Vec<Word> {
+        match word.split_assignment() {
+            Some(_) => expand_braces(word),
+            None => vec![word],
+        }
+    }
+
+    fn word(&mut self) -> Option<Word> {
+        match self.tokens.peek()? {
+            Token::Literal(_) | Token::Complex(_) => match self.tokens.next()? {
+                Token::Literal(text) => Some(Word::unquoted(&text)),
+                Token::Complex(word) => Some(word),
+                _ => unreachable!(),
+            },
+            _ => None,
+        }
+    }
+
+    fn redirect(&mut self) -> Result<Option<Redirect>, SyntaxError> {
+        let redirect = match self.tokens.peek() {
+            Some(Token::HereDoc { .. }) => match self.tokens.next() {
+                Some(Token::HereDoc { fd, body }) => Redirect::HereDoc { fd, body },
+                _ => unreachable!(),
+            },
+            Some(
+                Token::Write(_)
+                | Token::Append(_)
+                | Token::Read(_)
+                | Token::DupWrite(_)
+                | Token::DupRead(_)
+                | Token::WriteAll
+                | Token::AppendAll
+                | Token::HereString(_),
+            ) => {
+                let operator = self.tokens.next().unwrap();
+                let Some(target) = self.word() else {
+                    // Running out of input here is an error rather than incomplete
+                    return Err(match self.tokens.peek() {
+                        None => SyntaxError::UnexpectedToken("newline".to_string()),
+                        tk => Self::unexpected(tk),
+                    });
+                };
+
+                match operator {
+                    Token::Write(fd) => Redirect::Write { fd, target },
+                    Token::Append(fd) => Redirect::Append { fd, target },
+                    Token::Read(fd) => Redirect::Read { fd, target },
+                    Token::DupWrite(fd) | Token::DupRead(fd) => {
+                        match target.as_plain().as_deref() {
+                            Some("-") => Redirect::Close { fd },
+                            _ => Redirect::Duplicate { fd, source: target },
+                        }
+                    }
+                    Token::WriteAll => Redirect::WriteAll { target },
+                    Token::AppendAll => Redirect::AppendAll { target },
+                    Token::HereString(fd) => Redirect::HereString { fd, word: target },
+                    _ => unreachable!(),
+                }
+            }
+            _ => return Ok(None),
+        };
+
+        Ok(Some(redirect))
+    }
+
+    fn skip_newlines(&mut self) {
+        while self.tokens.next_if_eq(&Token::Newline).is_some() {}
+    }
+
+    fn expect_token(&mut self, expected: Token) -> Result<(), SyntaxError> {
+        match self.tokens.next() {
+            Some(tk) if tk == expected => Ok(()),
+            tk => Err(Self::unexpected(tk.as_ref())),
+        }
+    }
+
+    fn expect_word(&mut self, expected: &str) -> Result<(), SyntaxError> {
+        match self.tokens.next() {
+            Some(tk) if Self::reserved(&tk) == Some(expected) => Ok(()),
+            tk => Err(Self::unexpected(tk.as_ref())),
+        }
+    }
+
+    // A list that makes up part of a compound command, which can't be empty
+    fn non_empty(&mut self, until: &[&str]) -> Result<List, SyntaxError> {
+        let list = self.list(until)?;
+
+        match list.items.is_empty() {
+            true => Err(Self::unexpected(self.tokens.peek())),
+            false => Ok(list),
+        }
+    }
+
+    fn reserved(tk: &Token) -> Option<&'static str> {
+        match tk {
+            Token::Literal(text) => RESERVED.into_iter().find(|w| w == text),
+            _ => None,
+        }
+    }
+
+    fn unexpected(tk: Option<&Token>) -> SyntaxError {
+        let Some(tk) = tk else {
+            return SyntaxError::UnexpectedEof;
+        };
+
+        let text = match tk {
+            Token::Literal(text) => text.as_str(),
+            Token::Complex(word) => word.as_unquoted().unwrap_or("word"),
+            Token::Arith(_) => "((",
+            Token::Pipe => "|",
+            Token::Write(_) => ">",
+            Token::Append(_) => ">>",
+            Token::Read(_) => "<",
+            Token::DupWrite(_) => ">&",
+            Token::DupRead(_) => "<&",
+            Token::WriteAll => "&>",
+            Token::AppendAll => "&>>",
+            Token::HereDoc { .. } => "<<",
+            Token::HereString(_) => "<<<",
+            Token::Semi => ";",
+            Token::Amp => "&",
+            Token::And => "&&",
+            Token::Or => "||",
+            Token::Newline => "newline",
+            Token::LParen => "(",
+            Token::RParen => ")",
+        };
+
+        SyntaxError::UnexpectedToken(text.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+
+    fn parse_str(input: &str) -> Result<List, String> {
+        let mut lex = Lexer::new();
+        lex.push(input);
+
+        parse(lex.tokenize().unwrap()).map_err(|e| e.to_string())
+    }
+
+    fn connectors(input: &str) -> Result<Vec<Connector>, String> {
+        parse_str(input).map(|list| {
+            list.items
+                .iter()
+                .flat_map(|item| item.pipelines.iter().map(|(c, _)| *c))
+                .collect()
+        })
+    }
+
+    fn pipeline(list: &List, index: usize) -> &Pipeline {
+        &list.items[index].pipelines[0].1
+    }
+
+    fn simple(list: &List, index: usize) -> &SimpleCommand {
+        match &pipeline(list, index).commands[0] {
+            Command::Simple(simple) => simple,
+            command => panic!("not a simple command: {command:?}"),
+        }
+    }
+
+    #[test]
+    fn test_connectors() {
+        use Connector::*;
+
+        assert_eq!(Ok(vec![Always, And, Or]), connectors("a && b || c"));
+        assert_eq!(Ok(vec![Always, Always]), connectors("cd dir; ls;"));
+        assert_eq!(Ok(vec![Always, And]), connectors("a &&\n\nb\n"));
+        assert_eq!(Ok(vec![]), connectors(""));
+    }
+
+    #[test]
+    fn test_background() {
+        let list = parse_str("a && b & c\nd &").unwrap();
+        let background: Vec<_> = list.items.iter().map(|item| item.background).collect();
+        assert_eq!(vec![true, false, true], background);
+        assert_eq!(2, list.items[0].pipelines.len());
+    }
+
+    #[test]
+    fn test_display() {
+        let source = |input: &str| parse_str(input).unwrap().to_string();
+
+        assert_eq!("a && b | c & d", source("a&&b|c&d"));
+        assert_eq!(
+            "sleep 10 >/dev/null 2>&1",
+            source("sleep 10 > /dev/null 2>&1")
+        );
+        assert_eq!(
+            "echo 'a b' \"$x\" ${y}z ${n:-1}",
+            source("echo \"a b\" \"$x\" ${y}z ${n:-1}")
+        );
+        assert_eq!(
+            "for i in 1 2; do echo $i; done; (cd /)",
+            source("for i in 1 2\ndo echo $i\ndone\n( cd / )")
+        );
+        assert_eq!("let 'n < 3'", source("((n < 3))"));
+    }
+
+    #[test]
+    fn test_simple_command() {
+        let list = parse_str("A=1 B= cmd C=3 > out 2>&1 <<< word").unwrap();
+        let command = simple(&list, 0);
+
+        let names: Vec<_> = command.assignments.iter().map(|a| &a.name).collect();
+        assert_eq!(vec!["A", "B"], names);
+        assert_eq!(Word::unquoted("1"), command.assignments[0].value);
+        assert_eq!(
+            vec![Word::unquoted("cmd"), Word::unquoted("C=3")],
+            command.words
+        );
+        assert_eq!(
+            vec![
+                Redirect::Write {
+                    fd: 1,
+                    target: Word::unquoted("out")
+                },
+                Redirect::Duplicate {
+                    fd: 2,
+                    source: Word::unquoted("1")
+                },
+                Redirect::HereString {
+                    fd: 0,
+                    word: Word::unquoted("word")
+                },
+            ],
+            command.redirects
+        );
+
+        let list = parse_str("2>&- cmd").unwrap();
+        assert_eq!(vec![Redirect::Close { fd: 2 }], simple(&list, 0).redirects);
+
+        // Braces are left alone in an assignment only
+        let list = parse_str("A={a,b} echo A={a,b}").unwrap();
+        assert_eq!(
+            Word::unquoted("{a,b}"),
+            simple(&list, 0).assignments[0].value
+        );
+        assert_eq!(3, simple(&list, 0).words.len());
+
+        // A quoted name is looked up as a command, the value may be quoted
+        let list = parse_str("\"A=1\"; B='x y'").unwrap();
+        assert!(simple(&list, 0).assignments.is_empty());
+        assert_eq!("B", simple(&list, 1).assignments[0].name);
+    }
+
+    #[test]
+    fn test_compound_command() {
+        let list = parse_str("if a; then b; elif c\nthen d; else e; fi > log").unwrap();
+        let Command::Compound(
+            CompoundCommand::If {
+                branches,
+                otherwise,
+            },
+            redirects,
+        ) = &pipeline(&list, 0).commands[0]
+        else {
+            panic!("expected if");
+        };
+        assert_eq!(2, branches.len());
+        assert!(otherwise.is_some());
+        assert_eq!(1, redirects.len());
+
+        let list = parse_str("for f in *.rs; do echo $f; done | wc -l").unwrap();
+        let commands = &pipeline(&list, 0).commands;
+        assert_eq!(2, commands.len());
+        assert!(matches!(
+            &commands[0],
+            Command::Compound(CompoundCommand::For { name, words: Some(words), .. }, _)
+                if name == "f" && words.len() == 1
+        ));
+
+        let list = parse_str("while a; do b; done; until a; do b; done").unwrap();
+        assert!(matches!(
+            pipeline(&list, 1).commands[0],
+            Command::Compound(CompoundCommand::While { until: true, .. }, _)
+        ));
+
+        let list = parse_str("{ a; b; }; (cd dir && ls)").unwrap();
+        assert!(matches!(
+            pipeline(&list, 0).commands[0],
+            Command::Compound(CompoundCommand::Brace(_), _)
+        ));
+        assert!(matches!(
+            pipeline(&list, 1).commands[0],
+            Command::Compound(CompoundCommand::Subshell(_), _)
+        ));
+
+        // Reserved words are only special where a command starts
+        let list = parse_str("echo if done }").unwrap();
+        assert_eq!(4, simple(&list, 0).words.len());
+
+        // Nor when quoted
+        let list = parse_str("'if' true; \"done\"").unwrap();
+        assert_eq!(2, simple(&list, 0).words.len());
+        assert_eq!(1, simple(&list, 1).words.len());
+    }
+
+    #[test]
+    fn test_syntax_error() {
+        let unexpected = |token: &str| Err(format!("syntax error near unexpected token `{token}'"));
+        let eof = Err("syntax error: unexpected end of file".to_string());
+
+        assert_eq!(unexpected(";"), connectors("; ls"));
+        assert_eq!(unexpected("||"), connectors("a && || b"));
+        assert_eq!(unexpected("|"), connectors("a | | b"));
+        assert_eq!(unexpected("|"), connectors("| b"));
+        assert_eq!(unexpected(";"), connectors("echo > ;"));
+        assert_eq!(unexpected("fi"), connectors("if a; then fi"));
+        assert_eq!(unexpected("then"), connectors("then"));
+        assert_eq!(unexpected("then"), connectors("'if' true; then echo x; fi"));
+        assert_eq!(unexpected(")"), connectors("a )"));
+        assert_eq!(unexpected("b"), connectors("(a) b"));
+        assert_eq!(unexpected(";"), connectors("a & ;"));
+        assert_eq!(unexpected("&"), connectors("& a"));
+        assert_eq!(eof, connectors("a &&"));
+        assert_eq!(eof, connectors("a |"));
+        assert_eq!(eof, connectors("if a; then b"));
+        assert_eq!(eof, connectors("for x in a b; do"));
+        assert_eq!(eof, connectors("(a"));
+    }
+}
@@ -1,20 +1,47 @@
 use rustyline::completion::Completer;
 pub use rustyline::error::ReadlineError;
+use rustyline::highlight::CmdKind;
+use rustyline::validate::{ValidationContext, ValidationResult};
 
 use crate::context::ShellContext;
+use crate::lexer::{LexErrorKind, Lexer};
+use crate::parser::{self, SyntaxError};
+use std::borrow::Cow;
 use std::{fs, path::PathBuf};
 
 pub fn make_readline_helper(ctx: &ShellContext) -> ReadlineHelper {
     ReadlineHelper {
         builtin_names: ctx.builtin_names_iter().map(str::to_owned).collect(),
         paths: ctx.paths().collect(),
+        ps2: ctx.env("PS2").unwrap_or("> ").to_string(),
         // cwd: ctx.cwd().to_path_buf(),
     }
 }
 
+/// Whether `input` stops partway through a command, so that another line
+/// could complete it: an open quote, a trailing `|`, `&&` or backslash, an
+/// unfinished `if`/`for`/... or a here-document still missing its delimiter.
+pub fn is_incomplete(input: &str) -> bool {
+    let mut lex = Lexer::new();
+    lex.push(input);
+
+    match lex.tokenize() {
+        Ok(tokens) => {
+            Lexer::needs_heredoc_body(input)
+                || parser::parse(tokens) == Err(SyntaxError::UnexpectedEof)
+        }
+        Err(e) => matches!(
+            e.kind,
+            LexErrorKind::UnterminatedQuote(_) | LexErrorKind::DanglingEscape
+        ),
+    }
+}
+
 pub struct ReadlineHelper {
     builtin_names: Vec<String>,
     paths: Vec<PathBuf>,
+    // Shown in front of each continuation line
+    ps2: String,
     // cwd: PathBuf,
 }
 
@@ -174,5 +201,65 @@ impl rustyline::hint::Hinter for ReadlineHelper {
     }
 }
 
-impl rustyline::highlight::Highlighter for ReadlineHelper {}
-impl rustyline::validate::Validator for ReadlineHelper {}
+impl rustyline::highlight::Highlighter for ReadlineHelper {
+    fn highlight<'l>(&self, line: &'l str, _pos: usize) -> Cow<'l, str> {
+        match line.contains('\n') {
+            true => Cow::Owned(line.replace('\n', &format!("\n{}", self.ps2))),
+            false => Cow::Borrowed(line),
+        }
+    }
+
+    fn highlight_char(&self, line: &str, _pos: usize, _kind: CmdKind) -> bool {
+        line.contains('\n')
+    }
+}
+
+impl rustyline::validate::Validator for ReadlineHelper {
+    // Enter on an incomplete command starts a continuation line instead of
+    // running it, so the whole command is read as one line
+    fn validate(&self, ctx: &mut ValidationContext) -> rustyline::Result<ValidationResult> {
+        match is_incomplete(ctx.input()) {
+            true => Ok(ValidationResult::Incomplete),
+            false => Ok(ValidationResult::Valid(None)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_incomplete() {
+        for input in [
+            "echo 'abc",
+            "echo \"a\nb",
+            "ls |",
+            "make &&",
+            "echo a \\",
+            "if true; then",
+            "for f in a b\ndo echo $f",
+            "cat <<EOF\nline",
+            "echo $(ls",
+        ] {
+            assert!(is_incomplete(input), "{input:?}");
+        }
+
+        for input in ["echo 'a\nb'", "ls | wc", "echo a \\\nb", "echo >", "fi", ""] {
+            assert!(!is_incomplete(input), "{input:?}");
+        }
+    }
+
+    #[test]
+    fn test_continuation_prompt() {
+        use rustyline::highlight::Highlighter;
+
+        let mut ctx = ShellContext::new();
+        ctx.set_env("PS2", "... ");
+        let helper = make_readline_helper(&ctx);
+
+        assert_eq!("ls |", helper.highlight("ls |", 4));
+        assert_eq!("ls |\n... wc", helper.highlight("ls |\nwc", 4));
+        assert!(helper.highlight_char("if true\n", 8, CmdKind::Other));
+    }
+}
@@ -0,0 +1,266 @@
+use libc::{
+    O_CLOEXEC, O_NONBLOCK, POLLIN, SIG_DFL, SIG_ERR, SIG_IGN, SIGABRT, SIGALRM, SIGBUS, SIGCHLD,
+    SIGCONT, SIGFPE, SIGHUP, SIGILL, SIGINT, SIGIO, SIGKILL, SIGPIPE, SIGPROF, SIGQUIT, SIGSEGV,
+    SIGSTOP, SIGSYS, SIGTERM, SIGTRAP, SIGTSTP, SIGTTIN, SIGTTOU, SIGURG, SIGUSR1, SIGUSR2,
+    SIGVTALRM, SIGWINCH, SIGXCPU, SIGXFSZ, c_int, c_void, pipe2, poll, pollfd, read, sighandler_t,
+    signal, write,
+};
+use std::io;
+use std::sync::atomic::{AtomicI32, Ordering};
+
+// Number, name without the `SIG` prefix and the description shown when the
+// signal ends a job
+const SIGNALS: [(c_int, &str, &str); 29] = [
+    (SIGHUP, "HUP", "Hangup"),
+    (SIGINT, "INT", "Interrupted"),
+    (SIGQUIT, "QUIT", "Quit"),
+    (SIGILL, "ILL", "Illegal instruction"),
+    (SIGTRAP, "TRAP", "Trace/breakpoint trap"),
+    (SIGABRT, "ABRT", "Aborted"),
+    (SIGBUS, "BUS", "Bus error"),
+    (SIGFPE, "FPE", "Floating point exception"),
+    (SIGKILL, "KILL", "Killed"),
+    (SIGUSR1, "USR1", "User defined signal 1"),
+    (SIGSEGV, "SEGV", "Segmentation fault"),
+    (SIGUSR2, "USR2", "User defined signal 2"),
+    (SIGPIPE, "PIPE", "Broken pipe"),
+    (SIGALRM, "ALRM", "Alarm clock"),
+    (SIGTERM, "TERM", "Terminated"),
+    (SIGCHLD, "CHLD", "Child exited"),
+    (SIGCONT, "CONT", "Continued"),
+    (SIGSTOP, "STOP", "Stopped (signal)"),
+    (SIGTSTP, "TSTP", "Stopped"),
+    (SIGTTIN, "TTIN", "Stopped (tty input)"),
+    (SIGTTOU, "TTOU", "Stopped (tty output)"),
+    (SIGURG, "URG", "Urgent I/O condition"),
+    (SIGXCPU, "XCPU", "CPU time limit exceeded"),
+    (SIGXFSZ, "XFSZ", "File size limit exceeded"),
+    (SIGVTALRM, "VTALRM", "Virtual timer expired"),
+    (SIGPROF, "PROF", "Profiling timer expired"),
+    (SIGWINCH, "WINCH", "Window changed"),
+    (SIGIO, "IO", "I/O possible"),
+    (SIGSYS, "SYS", "Bad system call"),
+];
+
+/// Signals the terminal sends to the foreground job, which the shell itself
+/// ignores while it waits for one.
+pub const JOB_SIGNALS: [c_int; 3] = [SIGINT, SIGQUIT, SIGTSTP];
+
+/// Look a signal up by number or by name, with or without the `SIG` prefix
+/// and in any case.
+pub fn from_name(name: &str) -> Option<c_int> {
+    if let Ok(number) = name.parse::<c_int>() {
+        return SIGNALS
+            .iter()
+            .any(|(sig, ..)| *sig == number)
+            .then_some(number);
+    }
+
+    let upper = name.to_ascii_uppercase();
+    let short = upper.strip_prefix("SIG").unwrap_or(&upper);
+    SIGNALS
+        .iter()
+        .find(|(_, name, _)| *name == short)
+        .map(|(sig, ..)| *sig)
+}
+
+/// The name of a signal without the `SIG` prefix.
+pub fn name(sig: c_int) -> Option<&'static str> {
+    SIGNALS
+        .iter()
+        .find(|(number, ..)| *number == sig)
+        .map(|(_, name, _)| *name)
+}
+
+/// Every signal by number, with its name without the `SIG` prefix.
+pub fn all() -> impl Iterator<Item = (c_int, &'static str)> {
+    SIGNALS.iter().map(|(sig, name, _)| (*sig, *name))
+}
+
+/// What to report when `sig` ends a job, e.g. `Terminated`.
+pub fn describe(sig: c_int) -> String {
+    match SIGNALS.iter().find(|(number, ..)| *number == sig) {
+        Some((.., description)) => description.to_string(),
+        None => format!("Signal {sig}"),
+    }
+}
+
+/// Put back the default disposition of every signal an interactive shell
+/// changes, for a child that is about to run a command.
+pub fn reset_dispositions() {
+    for sig in [SIGINT, SIGQUIT, SIGTSTP, SIGTTIN, SIGTTOU] {
+        unsafe { signal(sig, SIG_DFL) };
+    }
+}
+
+// Both ends of the pipe caught signals are written to, -1 until the first
+// one is caught
+static PIPE_READ: AtomicI32 = AtomicI32::new(-1);
+static PIPE_WRITE: AtomicI32 = AtomicI32::new(-1);
+
+// Only records the signal, whatever it triggers runs once the shell checks
+// the pipe
+extern "C" fn on_signal(sig: c_int) {
+    let byte = sig as u8;
+    let fd = PIPE_WRITE.load(Ordering::Relaxed);
+    unsafe { write(fd, &byte as *const u8 as *const c_void, 1) };
+}
+
+/// Catch `sig` so that it shows up in `pending` instead of taking its
+/// default action.
+pub fn catch(sig: c_int) -> io::Result<()> {
+    open_pipe()?;
+    set_handler(sig, on_signal as extern "C" fn(c_int) as sighandler_t)
+}
+
+fn open_pipe() -> io::Result<()> {
+    if PIPE_WRITE.load(Ordering::Relaxed) == -1 {
+        let mut fds = [0; 2];
+        if unsafe { pipe2(fds.as_mut_ptr(), O_CLOEXEC | O_NONBLOCK) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        PIPE_READ.store(fds[0], Ordering::Relaxed);
+        PIPE_WRITE.store(fds[1], Ordering::Relaxed);
+    }
+
+    Ok(())
+}
+
+pub fn ignore(sig: c_int) -> io::Result<()> {
+    set_handler(sig, SIG_IGN)
+}
+
+pub fn restore_default(sig: c_int) -> io::Result<()> {
+    set_handler(sig, SIG_DFL)
+}
+
+fn set_handler(sig: c_int, handler: sighandler_t) -> io::Result<()> {
+    match unsafe { signal(sig, handler) } {
+        SIG_ERR => Err(io::Error::last_os_error()),
+        _ => Ok(()),
+    }
+}
+
+/// Signals caught since the last call, in the order they arrived.
+pub fn pending() -> Vec<c_int> {
+    let fd = PIPE_READ.load(Ordering::Relaxed);
+    if fd == -1 {
+        return Vec::new();
+    }
+
+    let mut signals = Vec::new();
+    let mut buf = [0u8; 64];
+    loop {
+        let n = unsafe { read(fd, buf.as_mut_ptr() as *mut c_void, buf.len()) };
+        if n <= 0 {
+            break;
+        }
+        signals.extend(buf[..n as usize].iter().map(|b| *b as c_int));
+    }
+
+    signals
+}
+
+/// Like `pending`, but blocks until at least one signal has been caught.
+pub fn wait_pending() -> Vec<c_int> {
+    let fd = PIPE_READ.load(Ordering::Relaxed);
+    if fd == -1 {
+        return Vec::new();
+    }
+
+    let mut fds = pollfd {
+        fd,
+        events: POLLIN,
+        revents: 0,
+    };
+    // Interrupted by the very signal that is then read from the pipe
+    unsafe { poll(&mut fds, 1, -1) };
+
+    pending()
+}
+
+/// Catches signals until dropped, when their old dispositions come back.
+/// Signals with a trap or ignored are left alone.
+pub struct CatchGuard {
+    saved: Vec<(c_int, sighandler_t)>,
+}
+
+impl CatchGuard {
+    pub fn new(signals: &[c_int]) -> io::Result<Self> {
+        open_pipe()?;
+
+        let handler = on_signal as extern "C" fn(c_int) as sighandler_t;
+        let mut saved = Vec::new();
+        for sig in signals {
+            match unsafe { signal(*sig, handler) } {
+                old @ (SIG_IGN | SIG_ERR) => unsafe {
+                    signal(*sig, old);
+                },
+                old if old == handler => {}
+                old => saved.push((*sig, old)),
+            }
+        }
+
+        Ok(Self { saved })
+    }
+}
+
+impl Drop for CatchGuard {
+    fn drop(&mut self) {
+        for (sig, handler) in self.saved.drain(..) {
+            unsafe { signal(sig, handler) };
+        }
+    }
+}
+
+/// Ignores signals until dropped, when their old dispositions come back.
+/// Signals the shell catches are left alone, a trap on them runs later.
+pub struct IgnoreGuard {
+    saved: Vec<(c_int, sighandler_t)>,
+}
+
+impl IgnoreGuard {
+    pub fn new(signals: &[c_int]) -> Self {
+        let mut saved = Vec::new();
+        for sig in signals {
+            match unsafe { signal(*sig, SIG_IGN) } {
+                SIG_DFL => saved.push((*sig, SIG_DFL)),
+                handler => unsafe {
+                    signal(*sig, handler);
+                },
+            }
+        }
+
+        Self { saved }
+    }
+}
+
+impl Drop for IgnoreGuard {
+    fn drop(&mut self) {
+        for (sig, handler) in self.saved.drain(..) {
+            unsafe { signal(sig, handler) };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_describe() {
+        assert_eq!("Interrupted", describe(SIGINT));
+        assert_eq!("Killed", describe(SIGKILL));
+        assert_eq!("Signal 64", describe(64));
+    }
+
+    #[test]
+    fn test_names() {
+        assert_eq!(Some(SIGINT), from_name("INT"));
+        assert_eq!(Some(SIGINT), from_name("sigint"));
+        assert_eq!(Some(SIGTERM), from_name("15"));
+        assert_eq!(None, from_name("NOPE"));
+        assert_eq!(None, from_name("0"));
+        assert_eq!(Some("USR1"), name(SIGUSR1));
+    }
+}
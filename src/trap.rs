@@ -0,0 +1,178 @@
+use std::collections::BTreeMap;
+use std::io;
+
+use libc::c_int;
+
+use crate::context::ShellContext;
+use crate::job::List;
+use crate::signal;
+
+/// What a trap is set on: a signal, or one of the shell's own events. Ordered
+/// the way `trap -p` lists them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Condition {
+    // The shell is exiting
+    Exit,
+    Signal(c_int),
+    // Before each command
+    Debug,
+    // A command failed
+    Err,
+}
+
+impl Condition {
+    /// Parse a condition as `trap` takes it: `EXIT` or `0`, `ERR`, `DEBUG`,
+    /// or a signal name or number.
+    pub fn parse(spec: &str) -> Option<Condition> {
+        match spec.to_ascii_uppercase().as_str() {
+            "EXIT" | "0" => Some(Condition::Exit),
+            "ERR" => Some(Condition::Err),
+            "DEBUG" => Some(Condition::Debug),
+            _ => signal::from_name(spec).map(Condition::Signal),
+        }
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            Condition::Exit => "EXIT".to_string(),
+            Condition::Signal(sig) => match signal::name(*sig) {
+                Some(name) => format!("SIG{name}"),
+                None => sig.to_string(),
+            },
+            Condition::Debug => "DEBUG".to_string(),
+            Condition::Err => "ERR".to_string(),
+        }
+    }
+}
+
+/// Commands set with `trap`, run when their condition comes up.
+#[derive(Default)]
+pub struct Traps {
+    actions: BTreeMap<Condition, String>,
+    // Set while an action runs, so that it can't set off another one
+    running: bool,
+    // Depth of `if` and `while` conditions being run, a failure there is
+    // expected and doesn't count for ERR
+    conditions: usize,
+}
+
+impl Traps {
+    /// Set the action to run for `condition`. `None` restores the default and
+    /// an empty action makes the shell ignore a signal.
+    pub fn set(&mut self, condition: Condition, action: Option<&str>) -> io::Result<()> {
+        if let Condition::Signal(sig) = condition {
+            match action {
+                None => signal::restore_default(sig)?,
+                Some("") => signal::ignore(sig)?,
+                Some(_) => signal::catch(sig)?,
+            }
+        }
+
+        match action {
+            Some(action) => self.actions.insert(condition, action.to_string()),
+            None => self.actions.remove(&condition),
+        };
+        Ok(())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Condition, &str)> {
+        self.actions.iter().map(|(c, action)| (*c, action.as_str()))
+    }
+
+    /// Forget every action that runs commands, for a forked subshell. Signals
+    /// the shell ignores stay ignored.
+    pub fn reset_for_subshell(&mut self) {
+        self.actions.retain(|condition, action| {
+            if let Condition::Signal(sig) = condition {
+                let _ = match action.is_empty() {
+                    true => signal::ignore(*sig),
+                    false => signal::restore_default(*sig),
+                };
+            }
+            action.is_empty()
+        });
+    }
+
+    pub fn enter_condition(&mut self) {
+        self.conditions += 1;
+    }
+
+    pub fn leave_condition(&mut self) {
+        self.conditions = self.conditions.saturating_sub(1);
+    }
+}
+
+/// Run the action set for `condition`, if any. `$?` is left as it was.
+pub fn run(condition: Condition, ctx: &mut ShellContext) {
+    let traps = ctx.traps();
+    if traps.running || (condition == Condition::Err && traps.conditions > 0) {
+        return;
+    }
+    let Some(action) = traps.actions.get(&condition).filter(|a| !a.is_empty()) else {
+        return;
+    };
+    let action = action.clone();
+
+    let status = ctx.status();
+    let pipestatus = ctx.pipestatus().to_vec();
+
+    ctx.traps_mut().running = true;
+    List::run_source(&action, ctx);
+    ctx.traps_mut().running = false;
+
+    ctx.set_status(status);
+    ctx.set_pipestatus(pipestatus);
+}
+
+/// Run the actions for the signals caught since the last check.
+pub fn run_pending(ctx: &mut ShellContext) {
+    // Left in the pipe until the running action is done
+    if ctx.traps().running {
+        return;
+    }
+
+    for sig in signal::pending() {
+        run(Condition::Signal(sig), ctx);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libc::{SIGINT, SIGUSR2};
+
+    #[test]
+    fn test_condition() {
+        assert_eq!(Some(Condition::Exit), Condition::parse("exit"));
+        assert_eq!(Some(Condition::Exit), Condition::parse("0"));
+        assert_eq!(Some(Condition::Signal(SIGINT)), Condition::parse("SIGINT"));
+        assert_eq!(Some(Condition::Err), Condition::parse("ERR"));
+        assert_eq!(None, Condition::parse("NOPE"));
+        assert_eq!("SIGINT", Condition::Signal(SIGINT).name());
+    }
+
+    #[test]
+    fn test_run() {
+        let mut ctx = ShellContext::new();
+        let sig = Condition::Signal(SIGUSR2);
+        ctx.traps_mut().set(sig, Some("got=1")).unwrap();
+        ctx.traps_mut().set(Condition::Err, Some("err=$?")).unwrap();
+
+        ctx.set_status(3);
+        run(sig, &mut ctx);
+        assert_eq!(Some("1"), ctx.env("got"));
+        assert_eq!(3, ctx.status());
+
+        List::run_source("if false; then true; fi; false && true; false", &mut ctx);
+        assert_eq!(Some("1"), ctx.env("err"));
+        ctx.unset_env("err");
+        List::run_source("{ false; } || true; true || false", &mut ctx);
+        assert_eq!(None, ctx.env("err"));
+
+        ctx.traps_mut().set(sig, None).unwrap();
+        assert_eq!(
+            vec![(Condition::Err, "err=$?")],
+            ctx.traps().iter().collect::<Vec<_>>()
+        );
+    }
+}